@@ -1,10 +1,21 @@
 use clap::Parser;
-use meta_gpt::terminal::{App, Commands};
-use meta_gpt::utils::{init_logger};
+use meta_gpt::error::GptError;
+use meta_gpt::model::{DownloadConfig, ModelGroup, ModelManifest, ModelSource, VerificationStatus};
+use meta_gpt::gpt::{draw_consensus_tree, AgentState, CohortPaths, DecisionTree, DiagnosticAgent, DiagnosticResult, PathOverlay, PromptLibrary, TreeFormat, TreeIssueSeverity};
+use meta_gpt::terminal::{App, Commands, ModelsCommands, TreeCommands};
+use meta_gpt::utils::{format_size, init_logger, write_tsv};
 use nvml_wrapper::Nvml;
+use clap::ValueEnum;
+use colored::Colorize;
+use indicatif::MultiProgress;
+use std::sync::Mutex;
 
 #[cfg(feature = "local")]
-use meta_gpt::text::{TextGenerator, GeneratorConfig};
+use meta_gpt::text::{TextGenerator, GeneratorConfig, GgufSummary, ModelPool};
+#[cfg(feature = "local")]
+use meta_gpt::gpt::{AneuploidyEvidence, ClinicalContext, ClinicalRecord, ContaminantRegistry, ExampleLibrary, SampleCatalogue, TaxaRenderer};
+#[cfg(feature = "local")]
+use cerebro_model::api::cerebro::schema::{PrefetchData, PostFilterConfig};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<(), anyhow::Error> {
@@ -31,22 +42,358 @@ async fn main() -> anyhow::Result<(), anyhow::Error> {
         },
         Commands::Download( args ) => {
             
-            let mut selected = args.models.clone();
-
-            if let Some(group) = args.group {
-                selected.extend(group.to_models());
-                selected.dedup(); // avoid duplicates
-            }
+            let selected = ModelGroup::select(&args.models, args.group);
 
             if selected.is_empty() {
                 log::error!("No models or model groups were selected!")
             }
 
-            for model in selected {
-                model.save_model(&args.outdir)?;
-                model.save_tokenizer(&args.outdir)?;
+            let source = ModelSource::new(
+                args.source,
+                args.endpoint.clone(),
+                args.path.clone(),
+                &cli.fs_url,
+                &cli.fs_port,
+                &args.fs_path,
+                cli.danger_invalid_certificate
+            )?;
+
+            let config = DownloadConfig {
+                retries: args.retries,
+                progress: (!args.no_progress).then(MultiProgress::new)
+            };
+
+            let queue = Mutex::new(selected.into_iter());
+
+            // Blocking clients must not run on the async runtime threads
+            tokio::task::block_in_place(|| {
+                std::thread::scope(|scope| -> Result<(), GptError> {
+                    let workers: Vec<_> = (0..args.jobs.max(1)).map(|_| {
+                        scope.spawn(|| -> Result<(), GptError> {
+                            loop {
+                                let next = queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).next();
+                                match next {
+                                    Some(model) => {
+                                        model.save_model_from(&args.outdir, &source, &config)?;
+                                        model.save_tokenizer_from(&args.outdir, &source, &config)?;
+                                    },
+                                    None => return Ok(())
+                                }
+                            }
+                        })
+                    }).collect();
+
+                    for worker in workers {
+                        worker.join().map_err(|panic| GptError::ModelDownloadWorkerPanicked(
+                            panic.downcast_ref::<&str>().map(|msg| msg.to_string())
+                                .or_else(|| panic.downcast_ref::<String>().cloned())
+                                .unwrap_or_default()
+                        ))??;
+                    }
+                    Ok(())
+                })
+            })?;
+
+        },
+        Commands::Verify( args ) => {
+
+            let selected = ModelGroup::select(&args.models, args.group);
+
+            let manifest = ModelManifest::from_json(
+                ModelManifest::path(&args.dir)
+            )?;
+
+            let report = manifest.verify(&args.dir, &selected)?;
+
+            for verification in &report {
+                match verification.status {
+                    VerificationStatus::Verified => log::info!("{}: verified ({})", verification.file, verification.expected.as_deref().unwrap_or("-")),
+                    _ => log::error!(
+                        "{}: {} (expected: {}, observed: {})", 
+                        verification.file, 
+                        serde_plain::to_string(&verification.status)?,
+                        verification.expected.as_deref().unwrap_or("-"), 
+                        verification.observed.as_deref().unwrap_or("-")
+                    )
+                }
+            }
+
+            if let Some(output) = &args.output {
+                serde_json::to_writer_pretty(std::fs::File::create(output)?, &report)?;
+            }
+
+            let failed = report.iter().filter(|v| v.status != VerificationStatus::Verified).count();
+            if failed > 0 {
+                return Err(GptError::ModelVerificationFailed(failed).into())
             }
 
+            log::info!("Verified {} file(s) against the lock manifest", report.len());
+        },
+        Commands::Models( subcommand ) => {
+            match subcommand {
+                ModelsCommands::List( args ) => {
+
+                    let groups = match args.group {
+                        Some(group) => vec![group],
+                        None => ModelGroup::value_variants().to_vec()
+                    };
+
+                    for group in groups {
+                        
+                        let status: Vec<_> = group.to_models()
+                            .iter()
+                            .map(|model| model.file_status(&args.dir))
+                            .filter(|status| !args.present || status.is_present())
+                            .collect();
+
+                        println!("{} ({}/{} present)", format!("{group:?}").bold().underline(), status.iter().filter(|s| s.is_present()).count(), status.len());
+
+                        for s in status {
+                            let size = |size: Option<u64>| size.map_or("missing".to_string(), |b| format_size(b as usize));
+                            let line = format!(
+                                "  {:<36} model: {:<12} tokenizer: {}",
+                                s.model.model_name(), 
+                                size(s.model_size), 
+                                size(s.tokenizer_size)
+                            );
+                            if s.is_present() { println!("{}", line.green()) } else { println!("{}", line.dimmed()) }
+                        }
+                        println!();
+                    }
+                },
+                #[cfg(feature = "local")]
+                ModelsCommands::Inspect( args ) => {
+                    let summary = GgufSummary::from_file(
+                        &args.dir.join(args.model.model_file())
+                    )?;
+                    summary.print(args.tensors);
+                }
+            }
+        },
+        Commands::Tree( subcommand ) => {
+            match subcommand {
+                TreeCommands::Export( args ) => {
+                    let prompts = PromptLibrary::from_option(args.prompts.as_deref())?;
+                    let tree = DecisionTree::from_file_or_config(args.tree_file.as_deref(), args.tree.clone(), args.task.clone(), &prompts)?;
+                    tree.to_format(
+                        &args.output, 
+                        args.format.unwrap_or(TreeFormat::from_path(&args.output))
+                    )?;
+                },
+                TreeCommands::Overlay( args ) => {
+                    let prompts = PromptLibrary::from_option(args.prompts.as_deref())?;
+                    let tree = DecisionTree::from_file_or_config(args.tree_file.as_deref(), args.tree.clone(), args.task.clone(), &prompts)?;
+
+                    let state = AgentState::from_json(&args.state)?;
+                    let result = match &args.result {
+                        Some(path) => Some(DiagnosticResult::from_json(path)?),
+                        None => None
+                    };
+
+                    let overlay = PathOverlay::new(&tree, &state, result.as_ref());
+
+                    log::info!("Execution path: {}", overlay.path.join(" -> "));
+
+                    let graph = match args.format.unwrap_or(TreeFormat::from_path(&args.output)) {
+                        TreeFormat::Mermaid => tree.to_mermaid_overlay(&overlay)?,
+                        _ => tree.to_dot_overlay(&overlay)?
+                    };
+                    std::fs::write(&args.output, graph)?;
+                },
+                TreeCommands::Consensus( args ) => {
+                    let prompts = PromptLibrary::from_option(args.prompts.as_deref())?;
+                    let tree = DecisionTree::from_file_or_config(args.tree_file.as_deref(), args.tree.clone(), args.task.clone(), &prompts)?;
+
+                    let cohort = CohortPaths::from_dir(&tree, &args.states)?;
+
+                    log::info!("Read {} agent states for decision tree: {}", cohort.samples.len(), tree.name);
+
+                    draw_consensus_tree(
+                        &DiagnosticAgent::graph(&tree)?,
+                        &cohort.graphs(&tree)?,
+                        Some(&tree.root_label()?),
+                        &args.output.to_string_lossy(),
+                        args.width,
+                        args.height
+                    )?;
+
+                    let frequencies = cohort.path_frequencies();
+                    for frequency in &frequencies {
+                        log::info!("{} ({}): {} samples", frequency.path, frequency.diagnosis, frequency.samples);
+                    }
+                    if let Some(path) = &args.paths {
+                        write_tsv(&frequencies, path, true)?;
+                    }
+                },
+                TreeCommands::Diff( args ) => {
+                    let prompts = PromptLibrary::from_option(args.prompts.as_deref())?;
+                    let base = DecisionTree::from_file_or_config(args.base_file.as_deref(), args.tree.clone(), args.task.clone(), &prompts)?;
+                    let tree = DecisionTree::from_file_with_prompts(&args.tree_file, &prompts)?;
+
+                    let changes = base.diff(&tree)?;
+
+                    println!("{} {} (v{}) -> {} (v{})", "Decision tree diff:".bold().underline(), base.name, base.version, tree.name, tree.version);
+
+                    for change in &changes {
+                        let header = format!(
+                            "{} {}{}", 
+                            serde_plain::to_string(&change.kind)?.yellow(), 
+                            change.node.as_deref().map(|node| format!("{node}.")).unwrap_or_default().bold().white(),
+                            change.field.bold().white()
+                        );
+                        match &change.diff {
+                            Some(diff) => {
+                                println!("\n{header}");
+                                for line in diff.lines() {
+                                    if line.starts_with('+') && !line.starts_with("+++") {
+                                        println!("{}", line.green())
+                                    } else if line.starts_with('-') && !line.starts_with("---") {
+                                        println!("{}", line.red())
+                                    } else {
+                                        println!("{line}")
+                                    }
+                                }
+                            },
+                            None => println!(
+                                "\n{header}: {} -> {}", 
+                                change.before.as_deref().unwrap_or("-").red(), 
+                                change.after.as_deref().unwrap_or("-").green()
+                            )
+                        }
+                    }
+
+                    if let Some(output) = &args.output {
+                        serde_json::to_writer_pretty(std::fs::File::create(output)?, &changes)?;
+                    }
+
+                    log::info!("Found {} change(s) between decision trees", changes.len());
+                },
+                TreeCommands::Validate( args ) => {
+                    let prompts = PromptLibrary::from_option(args.prompts.as_deref())?;
+                    let tree = DecisionTree::from_file_or_config(args.tree_file.as_deref(), args.tree.clone(), args.task.clone(), &prompts)?;
+
+                    let report = tree.validate()?;
+
+                    for issue in &report {
+                        let node = issue.node.as_deref().unwrap_or("-");
+                        match issue.severity {
+                            TreeIssueSeverity::Error => log::error!("{node}: {} ({})", issue.message, serde_plain::to_string(&issue.kind)?),
+                            TreeIssueSeverity::Warning => log::warn!("{node}: {} ({})", issue.message, serde_plain::to_string(&issue.kind)?)
+                        }
+                    }
+
+                    if let Some(output) = &args.output {
+                        serde_json::to_writer_pretty(std::fs::File::create(output)?, &report)?;
+                    }
+
+                    let failed = report.iter().filter(|issue| issue.is_error()).count();
+                    if failed > 0 {
+                        return Err(GptError::TreeValidationFailed(failed).into())
+                    }
+
+                    log::info!("Decision tree {} (v{}) is valid ({} node(s), {} warning(s))", tree.name, tree.version, tree.nodes.len(), report.len());
+                }
+            }
+        },
+        #[cfg(feature = "local")]
+        Commands::Diagnose( args ) => {
+
+            let prompts = PromptLibrary::from_option(args.prompts.as_deref())?;
+
+            let tree = DecisionTree::from_file_or_config(args.tree_file.as_deref(), args.tree.clone(), args.task.clone(), &prompts)?;
+
+            log::info!("Decision tree: {} (v{}) starting at: {}", tree.name, tree.version, tree.root_label()?);
+
+            let prefetch: PrefetchData = serde_json::from_str(
+                &std::fs::read_to_string(&args.prefetch)?
+            )?;
+
+            let aneuploidy = match &args.aneuploidy {
+                Some(path) => Some(AneuploidyEvidence::from_json(path)?),
+                None => None
+            };
+
+            let post_filter: Option<PostFilterConfig> = match &args.post_filter {
+                Some(path) => Some(serde_json::from_str(&std::fs::read_to_string(path)?)?),
+                None => None
+            };
+
+            // Node models are loaded on first use
+            let mut models = ModelPool::new(
+                GeneratorConfig::with_default(
+                    args.model, 
+                    args.model_dir.clone(), 
+                    args.sample_len, 
+                    args.temperature, 
+                    args.top_k, 
+                    args.top_p, 
+                    args.min_p, 
+                    args.gpu
+                )
+            );
+
+            let catalogue = match &args.sample_catalogue {
+                Some(path) => SampleCatalogue::from_file(path)?,
+                None => SampleCatalogue::builtin()
+            };
+            let sample_type = match args.sample_context.as_deref() {
+                None | Some("none") => None,
+                Some(id) => Some(catalogue.get(id)?.clone())
+            };
+
+            let clinical = match (&args.clinical, &args.sample_sheet) {
+                (Some(path), _) => Some(ClinicalContext::Record(ClinicalRecord::from_json(path)?)),
+                (None, Some(path)) => Some(ClinicalContext::from_sample_sheet(path, &prefetch.config.sample, &args.clinical_column)?),
+                (None, None) => args.clinical_notes.clone().map(ClinicalContext::Custom)
+            };
+            let clinical = match clinical {
+                Some(ClinicalContext::Record(record)) => {
+                    record.validate()?;
+                    Some(ClinicalContext::Record(record.ablate(&args.ablate)))
+                },
+                _ if !args.ablate.is_empty() => return Err(GptError::ClinicalAblationRecordMissing.into()),
+                clinical => clinical
+            };
+
+            let examples = match &args.examples {
+                Some(dir) => ExampleLibrary::from_dir(dir)?,
+                None => ExampleLibrary::default()
+            };
+
+            let contaminants = match &args.contaminants {
+                Some(path) => ContaminantRegistry::from_file(path)?.with_assay(args.contaminant_assay.clone()),
+                None => ContaminantRegistry::default()
+            };
+
+            let mut agent = DiagnosticAgent::from_tree(tree)?
+                .with_prompts(prompts)
+                .with_examples(examples)?
+                .with_renderer(TaxaRenderer {
+                    format: args.taxa_format,
+                    columns: args.taxa_columns.clone(),
+                    sort: args.taxa_sort
+                })
+                .with_contaminants(contaminants);
+
+            let result = agent.run_local(
+                prefetch,
+                aneuploidy,
+                &mut models,
+                sample_type,
+                clinical,
+                args.assay_context.clone(),
+                args.agent_primer.clone(),
+                post_filter,
+                args.disable_thinking
+            )?;
+
+            log::info!("Diagnosis: {} (pathogen: {})", result.diagnosis, result.pathogen.as_deref().unwrap_or("-"));
+
+            result.to_json(&args.output)?;
+
+            if let Some(path) = &args.state {
+                agent.state.to_json(path)?;
+            }
         }
     }
 
@@ -11,6 +11,8 @@ pub enum GptError {
     #[error(transparent)]
     SerdeJsonError(#[from] serde_json::Error),
     #[error(transparent)]
+    SerdeYamlError(#[from] serde_yaml::Error),
+    #[error(transparent)]
     HuggingfaceApiError(#[from] hf_hub::api::sync::ApiError),
     #[cfg(feature = "local")]
     #[error(transparent)]
@@ -29,14 +31,56 @@ pub enum GptError {
     CsvError(#[from] csv::Error),
     #[error(transparent)]
     RegexError(#[from] regex::Error),
+    #[error(transparent)]
+    TemplateError(#[from] minijinja::Error),
+    #[error(transparent)]
+    OpenAIError(#[from] async_openai::error::OpenAIError),
+    #[error(transparent)]
+    ReqwestError(#[from] reqwest::Error),
     #[error("plotters crate error: {0}")]
     PlottersError(#[from] Box<dyn std::error::Error + Send + Sync>), 
     #[error("decision tree root not found")]
     TreeRootMissing, 
+    #[error("decision tree root node is not defined: {0}")]
+    TreeRootUndefined(String), 
+    #[error("decision tree root is ambiguous, declare one of: {0}")]
+    TreeRootAmbiguous(String), 
     #[error("decision tree label not found")]
     TreeNodeLabelMissing, 
     #[error("decision tree question not found")]
     TreeNodeQuestionMissing, 
+    #[error("decision tree node {0} has no transition for: {1}")]
+    TreeTransitionMissing(String, String), 
+    #[error("decision tree validation failed with {0} error(s)")]
+    TreeValidationFailed(usize), 
+    #[error("decision tree node label is not unique: {0}")]
+    TreeNodeDuplicate(String), 
+    #[error("decision tree include requires a file or built-in tree: {0}")]
+    TreeIncludeSourceMissing(String), 
+    #[error("decision tree include {0} has no target for exit: {1}")]
+    TreeIncludeExitMissing(String, String), 
+    #[error("decision tree includes are nested too deep: {0}")]
+    TreeIncludeDepth(String), 
+    #[error("remote model is not supported for decision tree nodes: {0}")]
+    RemoteModelNotSupported(String), 
+    #[error("prompt library contains more than one prompt with identifier: {0}")]
+    PromptDuplicate(String), 
+    #[error("example library contains more than one example with identifier: {0}")]
+    ExampleDuplicate(String), 
+    #[error("example not found in the example library: {0}")]
+    ExampleMissing(String), 
+    #[error("sample type not found in the sample type catalogue: {0}")]
+    SampleTypeUnknown(String), 
+    #[error("clinical record is invalid: {0}")]
+    ClinicalRecordInvalid(String), 
+    #[error("clinical record fields can only be ablated from a structured clinical record")]
+    ClinicalAblationRecordMissing, 
+    #[error("taxa format is not a table format: {0}")]
+    TaxaFormatNotTabular(String), 
+    #[error("sample sheet column not found: {0}")]
+    SampleSheetColumnMissing(String), 
+    #[error("sample not found in sample sheet: {0}")]
+    SampleSheetSampleMissing(String), 
     #[error("end of sentence token not in vocabulary ({0})")]
     EosTokenNotInVocabulary(String), 
     #[error("sample identifier must be specified when not using prefetch data")]
@@ -45,6 +89,16 @@ pub enum GptError {
     NodeCheckTypeMissing, 
     #[error("Cerebro client not provided for diagnostic agent")]
     CerebroClientNotProvided, 
+    #[error("model lock manifest not found: {0}")]
+    ModelManifestMissing(String), 
+    #[error("model verification failed for {0} file(s)")]
+    ModelVerificationFailed(usize), 
+    #[error("model source requires a local directory or archive path")]
+    ModelSourcePathMissing, 
+    #[error("model file not found in source ({0}): {1}")]
+    ModelSourceFileMissing(String, String), 
+    #[error("model download worker panicked: {0}")]
+    ModelDownloadWorkerPanicked(String), 
 }
 
 impl<T> From<plotters::drawing::DrawingAreaErrorKind<T>> for GptError
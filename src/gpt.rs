@@ -1,9 +1,10 @@
 use anyhow::Result;
 use cerebro_model::api::cerebro::schema::{PrefetchData, PostFilterConfig};
-use cerebro_pipeline::taxa::taxon::{collapse_taxa, LineageOperations, Taxon};
+use cerebro_pipeline::taxa::taxon::{collapse_taxa, LineageOperations, ProfileRecord, Taxon};
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
 use std::hash::Hash;
 use std::io::{BufWriter, Write};
@@ -21,10 +22,12 @@ use petgraph::visit::IntoNodeReferences;
 use plotters::prelude::*;
 
 use crate::error::GptError;
+use crate::model::GeneratorModel;
+use crate::utils::{get_tsv_reader, sha256_str};
 
 
 #[cfg(feature = "local")]
-use crate::text::TextGenerator;
+use crate::text::ModelPool;
 
 //
 // === Refined Question Types ===
@@ -36,8 +39,11 @@ pub enum Question {
     Simple(String),
     Detailed {
         tasks: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
         data: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         context: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         instructions: Option<String>,
     },
 }
@@ -45,12 +51,15 @@ impl Question {
     /// [Context]
     /// ...
     /// 
+    /// [Examples]
+    /// ...
+    /// 
     /// [Tasks]
     /// ...
     ///
     /// [Instructions]
     /// ...
-    pub fn to_standard_prompt(&self, system: &Option<AgentPrimer>) -> String {
+    pub fn to_standard_prompt(&self, system: Option<&str>, examples: Option<&str>) -> String {
         let (tasks, data, context, instructions) = match self {
             Question::Simple(p) => (p.clone(), None, None, None),
             Question::Detailed { tasks, data, context, instructions } => {
@@ -61,7 +70,7 @@ impl Question {
         let mut out = String::new();
         if let Some(primer) = system {
             out.push_str("[System]\n");
-            out.push_str(&primer.text());
+            out.push_str(primer);
             out.push_str("\n\n");
         }
 
@@ -75,6 +84,11 @@ impl Question {
             out.push_str(&data);
             out.push_str("\n\n");
         }
+        if let Some(examples) = examples {
+            out.push_str("[Examples]\n");
+            out.push_str(examples);
+            out.push_str("\n\n");
+        }
 
         out.push_str("[Tasks]\n");
         out.push_str(&tasks);
@@ -89,6 +103,73 @@ impl Question {
     }
 }
 
+/// Prior node decision available to prompt templates
+#[derive(Debug, Clone, Serialize)]
+pub struct PriorAnswer {
+    pub result: Option<bool>,
+    pub category: Option<String>,
+    pub rule: Option<String>,
+    pub answer: Option<String>,
+    /// Number of taxa in the node data
+    pub taxa: usize
+}
+
+/// Variables of the node question templates and prompt layout templates - 
+/// undefined variables are errors
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptVariables {
+    pub node: String,
+    pub sample: String,
+    pub sample_type: Option<String>,
+    /// Sample type with the expected flora and typical pathogens
+    pub specimen: Option<SampleType>,
+    pub clinical_notes: Option<String>,
+    /// Structured clinical record
+    pub clinical: Option<ClinicalRecord>,
+    pub assay: String,
+    /// Combined assay, sample and clinical context block
+    pub context: String,
+    /// Candidate taxa blocks of the node data
+    pub data: String,
+    pub taxa: Vec<Taxon>,
+    /// Number of taxa by candidate block heading
+    pub counts: BTreeMap<String, usize>,
+    pub host_evidence: Option<String>,
+    /// Prior node decisions by node label
+    pub answers: BTreeMap<String, PriorAnswer>,
+    /// Few-shot examples of the node
+    pub examples: Option<String>
+}
+impl PromptVariables {
+    pub fn render(&self, template: &str) -> Result<String, GptError> {
+        Ok(Self::environment().render_str(template, self)?)
+    }
+    /// Render a prompt layout with the rendered question blocks and system primer
+    pub fn layout(&self, template: &str, question: &Question, system: Option<&str>) -> Result<String, GptError> {
+        let (tasks, data, context, instructions) = match question {
+            Question::Simple(p) => (p.clone(), None, None, None),
+            Question::Detailed { tasks, data, context, instructions } => {
+                (tasks.clone(), data.clone(), context.clone(), instructions.clone())
+            }
+        };
+        let variables = minijinja::context! {
+            system => system,
+            tasks => tasks,
+            context => context,
+            data => data,
+            instructions => instructions,
+            ..minijinja::Value::from_serialize(self)
+        };
+        Ok(Self::environment().render_str(template, variables)?)
+    }
+    fn environment() -> minijinja::Environment<'static> {
+        let mut env = minijinja::Environment::new();
+        env.set_undefined_behavior(minijinja::UndefinedBehavior::Strict);
+        env.set_keep_trailing_newline(true);
+        env
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, clap::ValueEnum)]
 pub enum GptStrategy {
     OneShotResponse,
@@ -121,6 +202,17 @@ impl ThresholdCandidates {
         Self { primary_threshold: None, secondary_threshold: None, target_threshold: None, integrate_threshold: None, combined_threshold: Some(taxa) }
     }
     pub fn to_str(&self, evidence: bool) -> String {
+        self.to_str_annotated(evidence, &BTreeMap::new())
+    }
+    /// Species data of a taxon with its laboratory background annotation
+    fn species_text(taxon: &Taxon, evidence: bool, annotations: &BTreeMap<String, String>) -> String {
+        match annotations.get(&taxon.taxid) {
+            Some(annotation) => format!("{}\nLaboratory background: {annotation}", taxon.species_data(evidence)),
+            None => taxon.species_data(evidence)
+        }
+    }
+    /// Candidates with annotations of taxa by taxid appended to their species data
+    pub fn to_str_annotated(&self, evidence: bool, annotations: &BTreeMap<String, String>) -> String {
         let mut output = String::new();
 
         // Process above threshold candidates
@@ -130,7 +222,7 @@ impl ThresholdCandidates {
             } else {
                 output.push_str("Primary threshold taxa:\n\n");
                 // Join the names of the taxa with commas.
-                let taxa: Vec<String> = above.iter().map(|taxon| taxon.species_data(evidence)).collect();
+                let taxa: Vec<String> = above.iter().map(|taxon| Self::species_text(taxon, evidence, annotations)).collect();
                 output.push_str(&taxa.join("\n\n"));
             }
             output.push('\n');
@@ -142,7 +234,7 @@ impl ThresholdCandidates {
                 output.push_str("No taxa detected.");
             } else {
                 output.push_str("Secondary threshold taxa:\n\n");
-                let taxa: Vec<String> = below.iter().map(|taxon| taxon.species_data(evidence)).collect();
+                let taxa: Vec<String> = below.iter().map(|taxon| Self::species_text(taxon, evidence, annotations)).collect();
                 output.push_str(&taxa.join("\n\n"));
             }
         }
@@ -153,7 +245,7 @@ impl ThresholdCandidates {
                 output.push_str("No taxa detected.");
             } else {
                 output.push_str("Target threshold taxa:\n\n");
-                let taxa: Vec<String> = target.iter().map(|taxon| taxon.species_data(evidence)).collect();
+                let taxa: Vec<String> = target.iter().map(|taxon| Self::species_text(taxon, evidence, annotations)).collect();
                 output.push_str(&taxa.join("\n\n"));
             }
         }
@@ -164,7 +256,7 @@ impl ThresholdCandidates {
                 output.push_str("No taxa detected.");
             } else {
                 output.push_str("Secondary and target threshold taxa:\n\n");
-                let taxa: Vec<String> = integrate.iter().map(|taxon| taxon.species_data(evidence)).collect();
+                let taxa: Vec<String> = integrate.iter().map(|taxon| Self::species_text(taxon, evidence, annotations)).collect();
                 output.push_str(&taxa.join("\n\n"));
             }
         }
@@ -176,7 +268,7 @@ impl ThresholdCandidates {
                 output.push_str("No taxa detected.");
             } else {
                 output.push_str("Primary, secondary and target threshold taxa:\n\n");
-                let taxa: Vec<String> = combined.iter().map(|taxon| taxon.species_data(evidence)).collect();
+                let taxa: Vec<String> = combined.iter().map(|taxon| Self::species_text(taxon, evidence, annotations)).collect();
                 output.push_str(&taxa.join("\n\n"));
             }
         }
@@ -185,6 +277,216 @@ impl ThresholdCandidates {
     }
 }
 
+/// Prompt file of a prompt library
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LibraryPrompt {
+    /// Prompt identifier e.g. `task.diagnose_default`
+    pub id: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub text: String
+}
+
+/// Built-in prompt text that can be replaced by a prompt library
+pub trait BuiltinPrompt {
+    fn prompt_id(&self) -> String;
+    fn builtin_text(&self) -> String;
+}
+
+/// Prompt identifier from the kind and the command-line name of a built-in prompt
+fn prompt_id<V: clap::ValueEnum>(kind: &str, value: &V) -> String {
+    format!("{kind}.{}", value_id(value))
+}
+
+fn value_id<V: clap::ValueEnum>(value: &V) -> String {
+    value.to_possible_value().map(|v| v.get_name().replace('-', "_")).unwrap_or_default()
+}
+
+/// Versioned prompts from a directory of prompt files (.yaml, .yml, .json) - 
+/// built-in prompts are resolved against the library and fall back to the 
+/// compiled text if the library has no prompt of the same identifier
+#[derive(Debug, Clone, Default)]
+pub struct PromptLibrary {
+    prompts: BTreeMap<String, LibraryPrompt>,
+    /// Versions of the resolved prompts by identifier
+    resolved: RefCell<BTreeMap<String, String>>
+}
+impl PromptLibrary {
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self, GptError> {
+        let mut library = Self::default();
+        library.read_dir(dir.as_ref())?;
+        log::info!("Prompt library: {} prompts from {}", library.prompts.len(), dir.as_ref().display());
+        Ok(library)
+    }
+    /// Prompt library of a directory if provided, otherwise the built-in prompts
+    pub fn from_option(dir: Option<&Path>) -> Result<Self, GptError> {
+        match dir {
+            Some(dir) => Self::from_dir(dir),
+            None => Ok(Self::default())
+        }
+    }
+    fn read_dir(&mut self, dir: &Path) -> Result<(), GptError> {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            if path.is_dir() {
+                self.read_dir(&path)?;
+                continue
+            }
+            let prompt = match path.extension().and_then(|e| e.to_str()) {
+                Some("yaml") | Some("yml") => serde_yaml::from_str::<LibraryPrompt>(&std::fs::read_to_string(&path)?)?,
+                Some("json") => serde_json::from_str::<LibraryPrompt>(&std::fs::read_to_string(&path)?)?,
+                _ => continue
+            };
+            if self.prompts.contains_key(&prompt.id) {
+                return Err(GptError::PromptDuplicate(prompt.id))
+            }
+            self.prompts.insert(prompt.id.clone(), prompt);
+        }
+        Ok(())
+    }
+    pub fn get(&self, id: &str) -> Option<&LibraryPrompt> {
+        self.prompts.get(id)
+    }
+    /// Prompt text from the library or the built-in text
+    pub fn resolve<P: BuiltinPrompt>(&self, prompt: &P) -> String {
+        let id = prompt.prompt_id();
+        let (version, text) = match self.prompts.get(&id) {
+            Some(prompt) => (prompt.version.clone(), prompt.text.clone()),
+            None => (String::from("builtin"), prompt.builtin_text())
+        };
+        self.resolved.borrow_mut().insert(id, version);
+        text
+    }
+    /// Versions of the prompts resolved so far by identifier
+    pub fn resolved(&self) -> BTreeMap<String, String> {
+        self.resolved.borrow().clone()
+    }
+}
+
+/// Curated example case with the adjudicated answer for few-shot prompts
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FewShotExample {
+    pub id: String,
+    /// Sample type identifiers the example applies to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sample_types: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    /// Candidate taxa block of the example
+    pub data: String,
+    pub answer: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rationale: Option<String>
+}
+impl FewShotExample {
+    pub fn to_str(&self, number: usize) -> String {
+        let mut text = format!("Example {number}:\n");
+        if let Some(context) = &self.context {
+            text.push_str(&format!("Context: {}\n", context.trim()));
+        }
+        text.push_str(&format!("Data:\n{}\n", self.data.trim()));
+        if let Some(rationale) = &self.rationale {
+            text.push_str(&format!("Rationale: {}\n", rationale.trim()));
+        }
+        text.push_str(&format!("Answer: {}\n", self.answer.trim()));
+        text
+    }
+}
+
+/// Few-shot examples of a node by identifier and by the sample type of the sample
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct NodeExamples {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ids: Vec<String>,
+    /// Include the examples of the sample type of the sample
+    #[serde(default)]
+    pub sample_type: bool,
+    /// Maximum number of examples in the prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<usize>
+}
+
+/// Few-shot examples from a directory of example files (.yaml, .yml, .json)
+#[derive(Debug, Clone, Default)]
+pub struct ExampleLibrary {
+    examples: BTreeMap<String, FewShotExample>
+}
+impl ExampleLibrary {
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self, GptError> {
+        let mut library = Self::default();
+        library.read_dir(dir.as_ref())?;
+        log::info!("Example library: {} examples from {}", library.examples.len(), dir.as_ref().display());
+        Ok(library)
+    }
+    fn read_dir(&mut self, dir: &Path) -> Result<(), GptError> {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            if path.is_dir() {
+                self.read_dir(&path)?;
+                continue
+            }
+            let example = match path.extension().and_then(|e| e.to_str()) {
+                Some("yaml") | Some("yml") => serde_yaml::from_str::<FewShotExample>(&std::fs::read_to_string(&path)?)?,
+                Some("json") => serde_json::from_str::<FewShotExample>(&std::fs::read_to_string(&path)?)?,
+                _ => continue
+            };
+            if self.examples.contains_key(&example.id) {
+                return Err(GptError::ExampleDuplicate(example.id))
+            }
+            self.examples.insert(example.id.clone(), example);
+        }
+        Ok(())
+    }
+    /// Check that the examples referenced by the tree nodes are in the library
+    pub fn check(&self, tree: &DecisionTree) -> Result<(), GptError> {
+        for (label, node) in tree.nodes.iter() {
+            for id in node.examples().iter().flat_map(|examples| &examples.ids) {
+                if !self.examples.contains_key(id) {
+                    return Err(GptError::ExampleMissing(format!("{id} (node: {label})")))
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Examples of the node - referenced examples first, then the examples of the sample type
+    pub fn select(&self, node: &NodeExamples, sample_type: Option<&str>) -> Result<Vec<&FewShotExample>, GptError> {
+        let mut selected = Vec::new();
+        for id in &node.ids {
+            selected.push(self.examples.get(id).ok_or(GptError::ExampleMissing(id.clone()))?);
+        }
+        if let (true, Some(sample_type)) = (node.sample_type, sample_type) {
+            for example in self.examples.values() {
+                if example.sample_types.iter().any(|t| t == sample_type) && !node.ids.contains(&example.id) {
+                    selected.push(example);
+                }
+            }
+        }
+        if let Some(max) = node.max {
+            selected.truncate(max);
+        }
+        Ok(selected)
+    }
+    /// Rendered examples block or none if the node has no examples
+    pub fn render(&self, node: &NodeExamples, sample_type: Option<&str>) -> Result<Option<String>, GptError> {
+        let examples = self.select(node, sample_type)?;
+        if examples.is_empty() {
+            return Ok(None)
+        }
+        Ok(Some(
+            examples.iter().enumerate().map(|(i, example)| example.to_str(i + 1)).collect::<Vec<_>>().join("\n").trim_end().to_string()
+        ))
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, clap::ValueEnum)]
 pub enum AgentPrimer {
     Default,
@@ -221,25 +523,330 @@ impl AssayContext {
     } 
 }
 
+impl BuiltinPrompt for AgentPrimer {
+    fn prompt_id(&self) -> String {
+        prompt_id("primer", self)
+    }
+    fn builtin_text(&self) -> String {
+        self.text()
+    }
+}
+impl BuiltinPrompt for AssayContext {
+    fn prompt_id(&self) -> String {
+        prompt_id("assay", self)
+    }
+    fn builtin_text(&self) -> String {
+        self.text()
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, clap::ValueEnum)]
 pub enum SampleContext {
     Csf,
     Eye,
     Tissue,
+    Blood,
+    Plasma,
+    Bal,
+    NasopharyngealSwab,
+    Stool,
+    JointFluid,
     Spike,
     None
 }
 
 impl SampleContext {
+    pub fn sample_type(&self) -> Option<String> {
+        self.profile().map(|profile| profile.name)
+    }
+    /// Built-in sample type with the expected flora and typical pathogens of the specimen
+    pub fn profile(&self) -> Option<SampleType> {
+        let id = clap::ValueEnum::to_possible_value(self).map(|v| v.get_name().to_string()).unwrap_or_default();
+        match self {
+            SampleContext::Csf => Some(SampleType::new(
+                &id,
+                "Cerebrospinal fluid (CSF)",
+                "Normally sterile specimen - any organism at high abundance is significant, low abundance skin and reagent organisms are common contaminants of the lumbar puncture and library preparation.",
+                &[],
+                &["Cutibacterium acnes", "coagulase-negative staphylococci", "Corynebacterium spp.", "Ralstonia spp.", "Bradyrhizobium spp."],
+                &["Streptococcus pneumoniae", "Neisseria meningitidis", "Listeria monocytogenes", "Haemophilus influenzae", "Streptococcus agalactiae", "Escherichia coli", "Herpes simplex virus 1/2", "Varicella-zoster virus", "Enteroviruses", "Cryptococcus neoformans", "Mycobacterium tuberculosis"]
+            )),
+            SampleContext::Eye => Some(SampleType::new(
+                &id,
+                "Vitreous fluid (VF)",
+                "Normally sterile specimen of low biomass - skin and conjunctival organisms are common contaminants of the vitreous tap.",
+                &[],
+                &["Cutibacterium acnes", "coagulase-negative staphylococci", "Corynebacterium spp."],
+                &["Cytomegalovirus", "Herpes simplex virus 1/2", "Varicella-zoster virus", "Toxoplasma gondii", "Staphylococcus aureus", "Streptococcus spp.", "Pseudomonas aeruginosa", "Candida albicans"]
+            )),
+            SampleContext::Tissue => Some(SampleType::new(
+                &id,
+                "Tissue (unknown source)",
+                "Tissue of unknown anatomical site - the expected flora depends on the site and whether it is normally sterile.",
+                &[],
+                &["Cutibacterium acnes", "coagulase-negative staphylococci"],
+                &[]
+            )),
+            SampleContext::Blood => Some(SampleType::new(
+                &id,
+                "Whole blood",
+                "Normally sterile specimen - skin organisms from venipuncture and reagent organisms are common contaminants, latent herpesviruses may be detected without active infection.",
+                &[],
+                &["coagulase-negative staphylococci", "Cutibacterium acnes", "Corynebacterium spp.", "Micrococcus spp.", "Torque teno virus"],
+                &["Staphylococcus aureus", "Escherichia coli", "Klebsiella pneumoniae", "Streptococcus pneumoniae", "Enterococcus faecalis", "Pseudomonas aeruginosa", "Candida spp.", "Plasmodium spp."]
+            )),
+            SampleContext::Plasma => Some(SampleType::new(
+                &id,
+                "Plasma (cell-free DNA)",
+                "Cell-free DNA of a normally sterile specimen - DNA of pathogens at distant sites of infection can be detected, anelloviruses and latent herpesvirus DNA are common and rarely significant alone.",
+                &["Torque teno virus"],
+                &["Cutibacterium acnes", "coagulase-negative staphylococci", "Ralstonia spp.", "Epstein-Barr virus (low level)"],
+                &["Staphylococcus aureus", "Escherichia coli", "Klebsiella pneumoniae", "Streptococcus spp.", "Pseudomonas aeruginosa", "Aspergillus fumigatus", "Pneumocystis jirovecii", "Cytomegalovirus", "Human adenovirus", "BK polyomavirus"]
+            )),
+            SampleContext::Bal => Some(SampleType::new(
+                &id,
+                "Bronchoalveolar lavage (BAL)",
+                "Lower respiratory specimen collected through the upper airway - oral and upper respiratory flora is expected and not significant unless dominant.",
+                &["Streptococcus mitis/oralis group", "Prevotella spp.", "Veillonella spp.", "Rothia spp.", "commensal Neisseria spp.", "Haemophilus parainfluenzae", "Candida spp. (colonization)"],
+                &["Ralstonia spp.", "Cutibacterium acnes"],
+                &["Streptococcus pneumoniae", "Haemophilus influenzae", "Staphylococcus aureus", "Pseudomonas aeruginosa", "Klebsiella pneumoniae", "Legionella pneumophila", "Mycoplasma pneumoniae", "Pneumocystis jirovecii", "Aspergillus fumigatus", "Mycobacterium tuberculosis", "Influenza virus", "Respiratory syncytial virus", "SARS-CoV-2"]
+            )),
+            SampleContext::NasopharyngealSwab => Some(SampleType::new(
+                &id,
+                "Nasopharyngeal swab",
+                "Upper respiratory specimen with a resident flora - asymptomatic carriage of respiratory bacteria is common, respiratory viruses are the main diagnostic target.",
+                &["Staphylococcus epidermidis", "Corynebacterium spp.", "Dolosigranulum pigrum", "Moraxella catarrhalis (carriage)", "Streptococcus pneumoniae (carriage)", "Haemophilus influenzae (carriage)"],
+                &["Cutibacterium acnes"],
+                &["Influenza A/B virus", "Respiratory syncytial virus", "SARS-CoV-2", "Human metapneumovirus", "Parainfluenza viruses", "Human adenovirus", "Rhinovirus/enterovirus", "Bordetella pertussis", "Mycoplasma pneumoniae"]
+            )),
+            SampleContext::Stool => Some(SampleType::new(
+                &id,
+                "Stool",
+                "Dense commensal gut microbiome - only recognized enteric pathogens are significant, commensal bacteria, bacteriophages and dietary DNA are expected.",
+                &["Bacteroides spp.", "Faecalibacterium prausnitzii", "Bifidobacterium spp.", "Escherichia coli (commensal)", "Enterococcus spp.", "Bacteriophages"],
+                &["Dietary plant and animal DNA", "Plant viruses"],
+                &["Salmonella enterica", "Campylobacter jejuni", "Shigella spp.", "Shiga toxin-producing Escherichia coli", "Clostridioides difficile", "Norovirus", "Rotavirus", "Human adenovirus 40/41", "Astrovirus", "Giardia intestinalis", "Cryptosporidium spp.", "Entamoeba histolytica"]
+            )),
+            SampleContext::JointFluid => Some(SampleType::new(
+                &id,
+                "Joint (synovial) fluid",
+                "Normally sterile specimen - skin organisms are common contaminants of the aspiration but are also pathogens of prosthetic joint infections.",
+                &[],
+                &["coagulase-negative staphylococci", "Cutibacterium acnes", "Corynebacterium spp."],
+                &["Staphylococcus aureus", "Streptococcus spp.", "Neisseria gonorrhoeae", "Kingella kingae", "Borrelia burgdorferi", "coagulase-negative staphylococci (prosthetic joint)", "Cutibacterium acnes (prosthetic joint)"]
+            )),
+            SampleContext::Spike => Some(SampleType::new(
+                &id,
+                "Spike-in sample (Laboratory)",
+                "Laboratory sample with spiked-in organisms at known concentrations.",
+                &[],
+                &[],
+                &[]
+            )),
+            SampleContext::None => None
+        }
+    }
     pub fn text(&self) -> String {
-        let sample_type = match self {
-            SampleContext::Csf => String::from("Cerebrospinal fluid (CSF)"),
-            SampleContext::Eye => String::from("Vitreous fluid (VF)"),
-            SampleContext::Tissue => String::from("Tissue (unknown source)"),
-            SampleContext::Spike => String::from("Spike-in sample (Laboratory)"),
-            SampleContext::None => String::from("No sample context provided.")
+        match self.profile() {
+            Some(profile) => profile.text(),
+            None => String::from("\n[Sample]\nNo sample context provided.\n\n")
+        }
+    }
+}
+
+/// Sample type with the background knowledge of the specimen for the [Sample] block
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SampleType {
+    pub id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Expected commensal flora of the specimen
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commensals: Vec<String>,
+    /// Common background and contaminant organisms
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub background: Vec<String>,
+    /// Typical pathogens of the specimen
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pathogens: Vec<String>
+}
+impl SampleType {
+    fn new(id: &str, name: &str, description: &str, commensals: &[&str], background: &[&str], pathogens: &[&str]) -> Self {
+        let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: Some(description.to_string()),
+            commensals: strings(commensals),
+            background: strings(background),
+            pathogens: strings(pathogens)
+        }
+    }
+    pub fn text(&self) -> String {
+        let mut text = format!("\n[Sample]\n{}\n", self.name);
+        if let Some(description) = &self.description {
+            text.push_str(&format!("\n{description}\n"));
+        }
+        for (heading, organisms) in [
+            ("Expected commensal flora", &self.commensals), 
+            ("Common background and contaminants", &self.background), 
+            ("Typical pathogens", &self.pathogens)
+        ] {
+            if !organisms.is_empty() {
+                text.push_str(&format!("{heading}: {}\n", organisms.join(", ")));
+            }
+        }
+        text.push('\n');
+        text
+    }
+}
+
+/// Sample types by identifier - the built-in sample types can be extended 
+/// or replaced by the sample types of a catalogue file (.yaml, .json)
+#[derive(Clone, Debug)]
+pub struct SampleCatalogue {
+    pub types: BTreeMap<String, SampleType>
+}
+impl SampleCatalogue {
+    pub fn builtin() -> Self {
+        Self {
+            types: <SampleContext as clap::ValueEnum>::value_variants()
+                .iter()
+                .filter_map(SampleContext::profile)
+                .map(|sample_type| (sample_type.id.clone(), sample_type))
+                .collect()
+        }
+    }
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, GptError> {
+        let data = std::fs::read_to_string(path.as_ref())?;
+        let sample_types: Vec<SampleType> = match is_yaml(path.as_ref()) {
+            true => serde_yaml::from_str(&data)?,
+            false => serde_json::from_str(&data)?
         };
-        format!("\n[Sample]\n{}\n\n", sample_type)
+        let mut catalogue = Self::builtin();
+        for sample_type in sample_types {
+            catalogue.types.insert(sample_type.id.clone(), sample_type);
+        }
+        Ok(catalogue)
+    }
+    pub fn get(&self, id: &str) -> Result<&SampleType, GptError> {
+        self.types.get(id).ok_or(GptError::SampleTypeUnknown(id.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContaminantSource {
+    Reagent,
+    Kit,
+    Environmental,
+    Skin,
+    Background
+}
+impl ContaminantSource {
+    pub fn text(&self) -> &'static str {
+        match self {
+            ContaminantSource::Reagent => "known reagent contaminant",
+            ContaminantSource::Kit => "known extraction-kit contaminant",
+            ContaminantSource::Environmental => "known environmental contaminant",
+            ContaminantSource::Skin => "known skin flora contaminant",
+            ContaminantSource::Background => "known laboratory background"
+        }
+    }
+}
+
+/// Historical RPM distribution of a contaminant in negative controls and routine samples
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RpmDistribution {
+    pub low: f64,
+    pub high: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub median: Option<f64>,
+    /// Number of historical samples of the distribution
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub samples: Option<u64>
+}
+
+/// Laboratory contaminant in the registry - entries without assays or sample 
+/// types apply to all assays or sample types
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ContaminantEntry {
+    /// Species name or taxid
+    pub taxon: String,
+    pub source: ContaminantSource,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assays: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sample_types: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rpm: Option<RpmDistribution>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>
+}
+impl ContaminantEntry {
+    /// Entry applies to the taxon by taxid or case-insensitive species name
+    pub fn matches(&self, taxid: &str, name: &str, assay: Option<&str>, sample_type: Option<&str>) -> bool {
+        let scoped = |values: &[String], value: Option<&str>| {
+            values.is_empty() || value.is_some_and(|value| values.iter().any(|v| v == value))
+        };
+        (self.taxon == taxid || self.taxon.eq_ignore_ascii_case(name))
+            && scoped(&self.assays, assay)
+            && scoped(&self.sample_types, sample_type)
+    }
+    /// Annotation of a matching taxon with the highest RPM of its profiling records 
+    /// e.g. "known extraction-kit contaminant, typical RPM 0-15"
+    pub fn annotate(&self, rpm_observed: f64) -> String {
+        let mut annotation = self.source.text().to_string();
+        if let Some(rpm) = &self.rpm {
+            annotation.push_str(&format!(", typical RPM {}-{}", rpm.low, rpm.high));
+            match (rpm.median, rpm.samples) {
+                (Some(median), Some(samples)) => annotation.push_str(&format!(" (median {median}, n = {samples})")),
+                (Some(median), None) => annotation.push_str(&format!(" (median {median})")),
+                (None, Some(samples)) => annotation.push_str(&format!(" (n = {samples})")),
+                (None, None) => {}
+            }
+            if rpm_observed > rpm.high {
+                annotation.push_str(", observed above the typical range");
+            }
+        }
+        if let Some(note) = &self.note {
+            annotation.push_str(&format!(", {note}"));
+        }
+        annotation
+    }
+}
+
+/// Registry of laboratory contaminants used to annotate the candidate taxa
+#[derive(Debug, Clone, Default)]
+pub struct ContaminantRegistry {
+    pub entries: Vec<ContaminantEntry>,
+    /// Assay identifier the entries are matched against
+    pub assay: Option<String>
+}
+impl ContaminantRegistry {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, GptError> {
+        let data = std::fs::read_to_string(path.as_ref())?;
+        let entries = match is_yaml(path.as_ref()) {
+            true => serde_yaml::from_str(&data)?,
+            false => serde_json::from_str(&data)?
+        };
+        Ok(Self { entries, assay: None })
+    }
+    pub fn with_assay(mut self, assay: Option<String>) -> Self {
+        self.assay = assay;
+        self
+    }
+    /// Annotations of the matching taxa by taxid - the first matching entry is used
+    pub fn annotations(&self, taxa: &[Taxon], assay: Option<&str>, sample_type: Option<&str>) -> BTreeMap<String, String> {
+        let assay = self.assay.as_deref().or(assay);
+        taxa.iter()
+            .filter_map(|taxon| {
+                self.entries.iter()
+                    .find(|entry| entry.matches(&taxon.taxid, &taxon.name, assay, sample_type))
+                    .map(|entry| (taxon.taxid.clone(), entry.annotate(TaxaRenderer::max_rpm(taxon))))
+            })
+            .collect()
     }
 }
 
@@ -247,6 +854,7 @@ impl SampleContext {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum ClinicalContext {
     Custom(String),
+    Record(ClinicalRecord),
     None
 }
 
@@ -254,10 +862,217 @@ impl ClinicalContext {
     pub fn text(&self) -> String {
         let clinical = match self {
             ClinicalContext::Custom(str)=> str.to_string(),
-            ClinicalContext::None => String::from("No clinical context provided.")
+            ClinicalContext::Record(record) if !record.is_empty() => record.to_str(),
+            _ => String::from("No clinical context provided.")
         };
         format!("\n[Clinical]\n{}\n\n", clinical)
     }
+    /// Clinical context of a sample from a sample sheet column (.tsv) with 
+    /// a clinical record (.json) or free-text clinical notes
+    pub fn from_sample_sheet(path: &Path, sample: &str, column: &str) -> Result<Self, GptError> {
+        let mut reader = get_tsv_reader(path, false, true)?;
+        let headers = reader.headers()?.clone();
+
+        let position = |name: &str| headers
+            .iter()
+            .position(|header| header == name)
+            .ok_or(GptError::SampleSheetColumnMissing(name.to_string()));
+        
+        let (sample_index, clinical_index) = (position("sample")?, position(column)?);
+
+        for record in reader.records() {
+            let record = record?;
+            if record.get(sample_index) != Some(sample) {
+                continue
+            }
+            let value = record.get(clinical_index).unwrap_or_default().trim();
+            return Ok(match value {
+                "" => ClinicalContext::None,
+                _ if value.starts_with('{') => ClinicalContext::Record(serde_json::from_str(value)?),
+                _ => ClinicalContext::Custom(value.to_string())
+            })
+        }
+        Err(GptError::SampleSheetSampleMissing(sample.to_string()))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AgeBand {
+    Neonate,
+    Infant,
+    Child,
+    Adolescent,
+    Adult,
+    Elderly
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImmuneStatus {
+    Immunocompetent,
+    Immunocompromised,
+    Neutropenic,
+    Transplant,
+    Hiv
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SymptomOnset {
+    Hyperacute,
+    Acute,
+    Subacute,
+    Chronic
+}
+
+/// Cerebrospinal fluid laboratory findings
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CsfFindings {
+    /// White cell count (cells/uL)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub white_cells: Option<f64>,
+    /// Protein (g/L)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protein: Option<f64>,
+    /// Glucose (mmol/L)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glucose: Option<f64>
+}
+
+/// Fields of the clinical record that can be removed for ablation experiments
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ClinicalField {
+    AgeBand,
+    ImmuneStatus,
+    Symptoms,
+    Onset,
+    Csf,
+    Imaging,
+    Antimicrobials,
+    Travel,
+    Notes
+}
+
+/// Structured clinical record of the sample rendered into the [Clinical] block
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClinicalRecord {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_band: Option<AgeBand>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub immune_status: Option<ImmuneStatus>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub symptoms: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub onset: Option<SymptomOnset>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub csf: Option<CsfFindings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub imaging: Option<String>,
+    /// Antimicrobial exposure before sampling
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub antimicrobials: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub travel: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>
+}
+impl ClinicalRecord {
+    pub fn from_json<P: AsRef<Path>>(path: P) -> Result<Self, GptError> {
+        let data = std::fs::read_to_string(path)?;
+        let record = serde_json::from_str::<ClinicalRecord>(&data)?;
+        Ok(record)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.to_str().is_empty()
+    }
+    /// Check that laboratory values are in plausible ranges and text fields are not blank
+    pub fn validate(&self) -> Result<(), GptError> {
+        let mut problems = Vec::new();
+
+        if let Some(csf) = &self.csf {
+            for (name, value, max) in [
+                ("CSF white cells", csf.white_cells, 100000.0), 
+                ("CSF protein", csf.protein, 50.0), 
+                ("CSF glucose", csf.glucose, 30.0)
+            ] {
+                if let Some(value) = value.filter(|value| !(0.0..=max).contains(value)) {
+                    problems.push(format!("{name} out of range (0 - {max}): {value}"));
+                }
+            }
+        }
+        if self.symptoms.iter().chain(&self.antimicrobials).any(|value| value.trim().is_empty()) {
+            problems.push("blank symptom or antimicrobial".to_string());
+        }
+        for (name, value) in [("imaging", &self.imaging), ("travel", &self.travel), ("notes", &self.notes)] {
+            if value.as_ref().is_some_and(|value| value.trim().is_empty()) {
+                problems.push(format!("blank {name}"));
+            }
+        }
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(GptError::ClinicalRecordInvalid(problems.join("; ")))
+        }
+    }
+    /// Remove fields from the record
+    pub fn ablate(mut self, fields: &[ClinicalField]) -> Self {
+        for field in fields {
+            match field {
+                ClinicalField::AgeBand => self.age_band = None,
+                ClinicalField::ImmuneStatus => self.immune_status = None,
+                ClinicalField::Symptoms => self.symptoms.clear(),
+                ClinicalField::Onset => self.onset = None,
+                ClinicalField::Csf => self.csf = None,
+                ClinicalField::Imaging => self.imaging = None,
+                ClinicalField::Antimicrobials => self.antimicrobials.clear(),
+                ClinicalField::Travel => self.travel = None,
+                ClinicalField::Notes => self.notes = None
+            }
+        }
+        self
+    }
+    pub fn to_str(&self) -> String {
+        let mut lines = Vec::new();
+
+        if let Some(age_band) = &self.age_band {
+            lines.push(format!("Age: {}", serde_plain::to_string(age_band).unwrap_or_default()));
+        }
+        if let Some(immune_status) = &self.immune_status {
+            lines.push(format!("Immune status: {}", serde_plain::to_string(immune_status).unwrap_or_default()));
+        }
+        if !self.symptoms.is_empty() {
+            lines.push(format!("Symptoms: {}", self.symptoms.join(", ")));
+        }
+        if let Some(onset) = &self.onset {
+            lines.push(format!("Onset: {}", serde_plain::to_string(onset).unwrap_or_default()));
+        }
+        if let Some(csf) = &self.csf {
+            let values: Vec<String> = [
+                csf.white_cells.map(|v| format!("white cells {v} cells/uL")),
+                csf.protein.map(|v| format!("protein {v} g/L")),
+                csf.glucose.map(|v| format!("glucose {v} mmol/L"))
+            ].into_iter().flatten().collect();
+            if !values.is_empty() {
+                lines.push(format!("CSF: {}", values.join(", ")));
+            }
+        }
+        if let Some(imaging) = &self.imaging {
+            lines.push(format!("Imaging: {imaging}"));
+        }
+        if !self.antimicrobials.is_empty() {
+            lines.push(format!("Antimicrobial exposure: {}", self.antimicrobials.join(", ")));
+        }
+        if let Some(travel) = &self.travel {
+            lines.push(format!("Travel: {travel}"));
+        }
+        if let Some(notes) = &self.notes {
+            lines.push(format!("Notes: {notes}"));
+        }
+        lines.join("\n")
+    }
 }
 
 //
@@ -275,18 +1090,722 @@ pub enum DiagnosticNode {
     BelowTargetThresholdQuery,
     DiagnoseInfectious,
     DiagnoseNonInfectious,
-    AboveSubThresholdQuery
+    DiagnoseTumor,
+    AboveSubThresholdQuery,
+    /// Node processed only by its node specification
+    Generic
+}
+impl DiagnosticNode {
+    /// Node specification of the built-in check types
+    pub fn spec(&self) -> Option<NodeSpec> {
+        let memory = |node: DiagnosticNode, label: CandidateLabel, positive: bool| MemoryInput {
+            node: serde_plain::to_string(&node).expect("DiagnosticNode serialization failed"),
+            label,
+            positive
+        };
+        let spec = match self {
+            DiagnosticNode::AboveThresholdQuery => NodeSpec::default()
+                .tiers(vec![DataTier::Primary]),
+            DiagnosticNode::BelowThresholdQuery => NodeSpec::default()
+                .tiers(vec![DataTier::Secondary]),
+            DiagnosticNode::TargetThresholdQuery => NodeSpec::default()
+                .tiers(vec![DataTier::Target]),
+            DiagnosticNode::BelowTargetThresholdQuery => NodeSpec::default()
+                .tiers(vec![DataTier::Secondary, DataTier::Target]),
+            DiagnosticNode::AboveSubThresholdQuery => NodeSpec {
+                required: vec![DataTier::Primary],
+                ..NodeSpec::default().tiers(vec![DataTier::Primary, DataTier::Secondary, DataTier::Target])
+            },
+            DiagnosticNode::IntegrateThresholds => NodeSpec {
+                memories: vec![
+                    memory(DiagnosticNode::BelowThresholdQuery, CandidateLabel::Secondary, false),
+                    memory(DiagnosticNode::TargetThresholdQuery, CandidateLabel::Target, false)
+                ],
+                reuse_single: true,
+                ..NodeSpec::default()
+            },
+            DiagnosticNode::DiagnoseInfectious => NodeSpec {
+                memories: vec![
+                    memory(DiagnosticNode::AboveThresholdQuery, CandidateLabel::Primary, true),
+                    memory(DiagnosticNode::AboveSubThresholdQuery, CandidateLabel::Combined, true),
+                    memory(DiagnosticNode::IntegrateThresholds, CandidateLabel::Integrate, true),
+                    memory(DiagnosticNode::BelowTargetThresholdQuery, CandidateLabel::Integrate, true)
+                ],
+                parser: NodeParser::Pathogen,
+                prompt_empty: true,
+                diagnosis: Some(Diagnosis::Infectious),
+                ..NodeSpec::default()
+            },
+            DiagnosticNode::DiagnoseNonInfectious => NodeSpec {
+                parser: NodeParser::None,
+                diagnosis: Some(Diagnosis::NonInfectious),
+                ..NodeSpec::default()
+            },
+            DiagnosticNode::AneuploidyQuery => NodeSpec {
+                aneuploidy: true,
+                ..NodeSpec::default()
+            },
+            DiagnosticNode::DiagnoseTumor => NodeSpec {
+                parser: NodeParser::None,
+                diagnosis: Some(Diagnosis::Tumor),
+                ..NodeSpec::default()
+            },
+            DiagnosticNode::Generic => return None
+        };
+        Some(spec)
+    }
+}
+
+/// Copy-number alteration of a chromosome or chromosome segment 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CopyNumberSegment {
+    pub chromosome: String,
+    /// Chromosome arm or cytoband (e.g. p, q, 8q24)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<u64>,
+    pub copy_number: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub z_score: Option<f64>,
+}
+
+/// Host copy-number or aneuploidy evidence from the human reads of 
+/// the sample, supplied alongside the prefetched taxa
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct AneuploidyEvidence {
+    /// Method used to call the copy-number profile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// Genome-wide aneuploidy score (e.g. fraction of the genome altered)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    #[serde(default)]
+    pub segments: Vec<CopyNumberSegment>,
+}
+impl AneuploidyEvidence {
+    pub fn from_json<P: AsRef<Path>>(path: P) -> Result<Self, GptError> {
+        let data = std::fs::read_to_string(path)?;
+        let evidence = serde_json::from_str::<AneuploidyEvidence>(&data)?;
+        Ok(evidence)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.score.is_none() && self.segments.is_empty()
+    }
+    pub fn to_str(&self) -> String {
+        let mut output = String::from("Host copy-number profile");
+        if let Some(method) = &self.method {
+            output.push_str(&format!(" ({method})"));
+        }
+        output.push_str(":\n\n");
+
+        if let Some(score) = self.score {
+            output.push_str(&format!("Aneuploidy score: {score:.4}\n"));
+        }
+        if self.segments.is_empty() {
+            output.push_str("No copy-number alterations detected.\n");
+        }
+        for segment in &self.segments {
+            let mut line = format!("chr{}{}", segment.chromosome.trim_start_matches("chr"), segment.region.as_deref().unwrap_or(""));
+            if let (Some(start), Some(end)) = (segment.start, segment.end) {
+                line.push_str(&format!(":{start}-{end}"));
+            }
+            line.push_str(&format!(" copy number {:.2}", segment.copy_number));
+            if let Some(z_score) = segment.z_score {
+                line.push_str(&format!(" (z-score {z_score:.2})"));
+            }
+            output.push_str(&line);
+            output.push('\n');
+        }
+        output
+    }
+}
+
+/// Prefetched data tiers of the filtered taxonomic profiling data
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DataTier {
+    Primary,
+    Secondary,
+    Target
+}
+impl DataTier {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataTier::Primary => "Primary",
+            DataTier::Secondary => "Secondary",
+            DataTier::Target => "Target"
+        }
+    }
+    pub fn label(&self) -> CandidateLabel {
+        match self {
+            DataTier::Primary => CandidateLabel::Primary,
+            DataTier::Secondary => CandidateLabel::Secondary,
+            DataTier::Target => CandidateLabel::Target
+        }
+    }
+    pub fn taxa(&self, prefetch: &PrefetchData) -> Vec<Taxon> {
+        match self {
+            DataTier::Primary => prefetch.primary.clone(),
+            DataTier::Secondary => prefetch.secondary.clone(),
+            DataTier::Target => prefetch.target.clone()
+        }
+    }
+}
+
+/// Heading of a candidate taxa block in the prompt data - any other 
+/// value than the built-in labels is used as custom heading 
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, Hash, PartialEq)]
+#[serde(from = "String", into = "String")]
+pub enum CandidateLabel {
+    Primary,
+    Secondary,
+    Target,
+    Integrate,
+    Combined,
+    Custom(String)
+}
+impl From<String> for CandidateLabel {
+    fn from(label: String) -> Self {
+        match label.as_str() {
+            "primary" => CandidateLabel::Primary,
+            "secondary" => CandidateLabel::Secondary,
+            "target" => CandidateLabel::Target,
+            "integrate" => CandidateLabel::Integrate,
+            "combined" => CandidateLabel::Combined,
+            _ => CandidateLabel::Custom(label)
+        }
+    }
+}
+impl From<CandidateLabel> for String {
+    fn from(label: CandidateLabel) -> Self {
+        match label {
+            CandidateLabel::Primary => "primary".to_string(),
+            CandidateLabel::Secondary => "secondary".to_string(),
+            CandidateLabel::Target => "target".to_string(),
+            CandidateLabel::Integrate => "integrate".to_string(),
+            CandidateLabel::Combined => "combined".to_string(),
+            CandidateLabel::Custom(heading) => heading
+        }
+    }
+}
+impl CandidateLabel {
+    pub fn heading(&self) -> String {
+        match self {
+            CandidateLabel::Primary => "Primary threshold taxa".to_string(),
+            CandidateLabel::Secondary => "Secondary threshold taxa".to_string(),
+            CandidateLabel::Target => "Target threshold taxa".to_string(),
+            CandidateLabel::Integrate => "Secondary and target threshold taxa".to_string(),
+            CandidateLabel::Combined => "Primary, secondary and target threshold taxa".to_string(),
+            CandidateLabel::Custom(heading) => heading.clone()
+        }
+    }
+    /// Candidate taxa block in the format of the renderer with annotations of taxa by taxid - 
+    /// blocks without taxa keep the heading so that prompts have the same structure in all formats
+    pub fn render_with(&self, taxa: Vec<Taxon>, renderer: &TaxaRenderer, annotations: &BTreeMap<String, String>) -> Result<String, GptError> {
+        if taxa.is_empty() {
+            return Ok(format!("{}:\n\nNo taxa detected.", self.heading()))
+        }
+        let taxa = renderer.sorted(taxa);
+        let annotations: BTreeMap<String, String> = annotations.iter()
+            .filter(|(taxid, _)| taxa.iter().any(|taxon| &taxon.taxid == *taxid))
+            .map(|(taxid, annotation)| (taxid.clone(), annotation.clone()))
+            .collect();
+        match renderer.format {
+            TaxaFormat::Prose => Ok(self.render_annotated(taxa, &annotations)),
+            _ => Ok(format!("{}:\n\n{}", self.heading(), renderer.table(&taxa, &annotations)?))
+        }
+    }
+    pub fn render(&self, taxa: Vec<Taxon>) -> String {
+        self.render_annotated(taxa, &BTreeMap::new())
+    }
+    /// Prose candidate taxa block with annotations of taxa by taxid
+    pub fn render_annotated(&self, taxa: Vec<Taxon>, annotations: &BTreeMap<String, String>) -> String {
+        match self {
+            CandidateLabel::Primary => ThresholdCandidates::from_primary_threshold(taxa).to_str_annotated(true, annotations),
+            CandidateLabel::Secondary => ThresholdCandidates::from_secondary_threshold(taxa).to_str_annotated(true, annotations),
+            CandidateLabel::Target => ThresholdCandidates::from_target_threshold(taxa).to_str_annotated(true, annotations),
+            CandidateLabel::Integrate => ThresholdCandidates::from_integrate_threshold(taxa).to_str_annotated(true, annotations),
+            CandidateLabel::Combined => ThresholdCandidates::from_combined_threshold(taxa).to_str_annotated(true, annotations),
+            CandidateLabel::Custom(heading) => {
+                if taxa.is_empty() {
+                    format!("{heading}:\n\nNo taxa detected.")
+                } else {
+                    let taxa: Vec<String> = taxa.iter().map(|taxon| ThresholdCandidates::species_text(taxon, true, annotations)).collect();
+                    format!("{heading}:\n\n{}", taxa.join("\n\n"))
+                }
+            }
+        }
+    }
+}
+
+/// Output formats of the candidate taxa in the prompt data
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, Eq, Hash, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxaFormat {
+    #[default]
+    Prose,
+    Markdown,
+    Csv,
+    Json
+}
+
+/// Columns of the tabular taxa formats - tool columns are expanded to one 
+/// column for each profiling tool with evidence in the block
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, Hash, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxaColumn {
+    Name,
+    Taxid,
+    Domain,
+    Lineage,
+    /// Reads per million for each tool
+    Rpm,
+    /// Reads for each tool
+    Reads,
+    Contigs,
+    Bases,
+    /// Evidence score of the profiling records
+    Score
+}
+
+/// Order of the candidate taxa in the prompt data
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, Eq, Hash, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxaSort {
+    #[default]
+    Input,
+    Score,
+    Rpm,
+    Name
+}
+
+/// Rendering of the candidate taxa in the prompt data
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, Hash, PartialEq)]
+pub struct TaxaRenderer {
+    #[serde(default)]
+    pub format: TaxaFormat,
+    #[serde(default = "TaxaRenderer::default_columns")]
+    pub columns: Vec<TaxaColumn>,
+    #[serde(default)]
+    pub sort: TaxaSort
+}
+impl Default for TaxaRenderer {
+    fn default() -> Self {
+        Self {
+            format: TaxaFormat::default(),
+            columns: Self::default_columns(),
+            sort: TaxaSort::default()
+        }
+    }
+}
+impl TaxaRenderer {
+    fn default_columns() -> Vec<TaxaColumn> {
+        vec![TaxaColumn::Name, TaxaColumn::Domain, TaxaColumn::Rpm, TaxaColumn::Contigs, TaxaColumn::Bases]
+    }
+    fn score(taxon: &Taxon) -> f64 {
+        taxon.evidence.profile_score(1.0)
+    }
+    fn max_rpm(taxon: &Taxon) -> f64 {
+        taxon.evidence.records.iter().map(|record| record.rpm).fold(0.0, f64::max)
+    }
+    pub fn sorted(&self, mut taxa: Vec<Taxon>) -> Vec<Taxon> {
+        match self.sort {
+            TaxaSort::Input => {},
+            TaxaSort::Score => taxa.sort_by(|a, b| Self::score(b).total_cmp(&Self::score(a))),
+            TaxaSort::Rpm => taxa.sort_by(|a, b| Self::max_rpm(b).total_cmp(&Self::max_rpm(a))),
+            TaxaSort::Name => taxa.sort_by(|a, b| a.name.cmp(&b.name))
+        }
+        taxa
+    }
+    /// Column headers and values of the selected columns
+    fn columns(&self, taxa: &[Taxon], annotations: &BTreeMap<String, String>) -> Vec<(String, Vec<serde_json::Value>)> {
+        let mut tools = Vec::new();
+        for record in taxa.iter().flat_map(|taxon| &taxon.evidence.records) {
+            let tool = serde_plain::to_string(&record.tool).unwrap_or_default().to_lowercase();
+            if !tools.contains(&tool) {
+                tools.push(tool);
+            }
+        }
+        let tool_sum = |taxon: &Taxon, tool: &str, value: fn(&ProfileRecord) -> f64| -> serde_json::Value {
+            let records: Vec<&ProfileRecord> = taxon.evidence.records
+                .iter()
+                .filter(|record| serde_plain::to_string(&record.tool).unwrap_or_default().to_lowercase() == tool)
+                .collect();
+            match records.is_empty() {
+                true => serde_json::Value::Null,
+                false => serde_json::json!(round(records.iter().map(|record| value(record)).sum(), 2))
+            }
+        };
+
+        let mut columns = Vec::new();
+        for column in &self.columns {
+            match column {
+                TaxaColumn::Rpm | TaxaColumn::Reads => for tool in &tools {
+                    let (header, value): (String, fn(&ProfileRecord) -> f64) = match column {
+                        TaxaColumn::Rpm => (format!("rpm_{tool}"), |record| record.rpm),
+                        _ => (format!("reads_{tool}"), |record| record.reads as f64)
+                    };
+                    columns.push((header, taxa.iter().map(|taxon| tool_sum(taxon, tool, value)).collect()));
+                },
+                _ => {
+                    let header = serde_plain::to_string(column).unwrap_or_default();
+                    let values = taxa.iter().map(|taxon| match column {
+                        TaxaColumn::Name => serde_json::json!(taxon.name),
+                        TaxaColumn::Taxid => serde_json::json!(taxon.taxid),
+                        TaxaColumn::Domain => serde_json::json!(taxon.lineage.get_domain()),
+                        TaxaColumn::Lineage => serde_json::json!(taxon.lineage),
+                        TaxaColumn::Contigs => serde_json::json!(taxon.evidence.records.iter().map(|record| record.contigs).sum::<u64>()),
+                        TaxaColumn::Bases => serde_json::json!(taxon.evidence.records.iter().map(|record| record.bases).sum::<u64>()),
+                        TaxaColumn::Score => serde_json::json!(round(Self::score(taxon), 2)),
+                        TaxaColumn::Rpm | TaxaColumn::Reads => serde_json::Value::Null
+                    }).collect();
+                    columns.push((header, values));
+                }
+            }
+        }
+        if !annotations.is_empty() {
+            columns.push(("background".to_string(), taxa.iter().map(|taxon| serde_json::json!(annotations.get(&taxon.taxid))).collect()));
+        }
+        columns
+    }
+    /// Taxa in the tabular format of the renderer - annotated taxa add a background column, 
+    /// prose is rendered by the candidate label (`CandidateLabel::render_with`)
+    pub fn table(&self, taxa: &[Taxon], annotations: &BTreeMap<String, String>) -> Result<String, GptError> {
+        self.tabulate(&self.columns(taxa, annotations), taxa.len())
+    }
+    /// Column headers and values in the tabular format of the renderer
+    fn tabulate(&self, columns: &[(String, Vec<serde_json::Value>)], size: usize) -> Result<String, GptError> {
+        let cell = |value: &serde_json::Value| match value {
+            serde_json::Value::Null => String::new(),
+            serde_json::Value::String(value) => value.clone(),
+            value => value.to_string()
+        };
+        let rows: Vec<Vec<String>> = (0..size)
+            .map(|i| columns.iter().map(|(_, values)| cell(&values[i])).collect())
+            .collect();
+
+        match self.format {
+            TaxaFormat::Markdown => {
+                let mut table = format!("| {} |\n", columns.iter().map(|(header, _)| header.as_str()).collect::<Vec<_>>().join(" | "));
+                table.push_str(&format!("|{}\n", "---|".repeat(columns.len())));
+                for row in rows {
+                    let row: Vec<String> = row.into_iter().map(|value| match value.is_empty() {
+                        true => "-".to_string(),
+                        false => value.replace('|', "\\|")
+                    }).collect();
+                    table.push_str(&format!("| {} |\n", row.join(" | ")));
+                }
+                Ok(table.trim_end().to_string())
+            },
+            TaxaFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(Vec::new());
+                writer.write_record(columns.iter().map(|(header, _)| header))?;
+                for row in rows {
+                    writer.write_record(row)?;
+                }
+                let data = writer.into_inner().map_err(|err| GptError::IoError(err.into_error()))?;
+                Ok(String::from_utf8_lossy(&data).trim_end().to_string())
+            },
+            TaxaFormat::Json => {
+                let records: Vec<serde_json::Value> = (0..size).map(|i| {
+                    serde_json::Value::Object(columns.iter().map(|(header, values)| (header.clone(), values[i].clone())).collect())
+                }).collect();
+                Ok(serde_json::to_string(&records)?)
+            },
+            TaxaFormat::Prose => Err(GptError::TaxaFormatNotTabular("prose".to_string()))
+        }
+    }
+}
+
+fn round(value: f64, digits: i32) -> f64 {
+    let factor = 10f64.powi(digits);
+    (value * factor).round() / factor
+}
+
+/// Parser applied to the model answer of a node
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeParser {
+    /// Yes or no decision in <result></result> tags
+    Result,
+    /// Pathogen and candidates in <pathogen></pathogen> and <candidate></candidate> tags
+    Pathogen,
+    /// One of the node branch categories in <category></category> tags
+    Category,
+    /// No answer is parsed
+    None
+}
+impl NodeParser {
+    /// Answer tags the parser extracts from the model output
+    pub fn expected_tags(&self) -> Vec<&'static str> {
+        match self {
+            NodeParser::Result => vec!["result"],
+            NodeParser::Pathogen => vec!["pathogen"],
+            NodeParser::Category => vec!["category"],
+            NodeParser::None => vec![]
+        }
+    }
+}
+
+/// Rule quantifier over the taxa of the node data
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleMatch {
+    /// At least one taxon matches the condition
+    Any,
+    /// All taxa match the condition (at least one taxon required)
+    All,
+    /// No taxon matches the condition
+    None
+}
+
+/// Conditions on the evidence and lineage of a taxon, all specified conditions must hold
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct TaxonCondition {
+    /// Taxon names (species)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub names: Vec<String>,
+    /// Lineage domains (e.g. Viruses, Bacteria)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub domains: Vec<String>,
+    /// Viral taxon in the post-filter phage list or named as phage
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phage: Option<bool>,
+    /// Minimum reads per million of any profile record
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_rpm: Option<f64>,
+    /// Minimum reads of any profile record
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_reads: Option<u64>,
+    /// Minimum assembled contigs of any profile record
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_contigs: Option<u64>,
+}
+impl Eq for TaxonCondition {}
+impl Hash for TaxonCondition {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.names.hash(state);
+        self.domains.hash(state);
+        self.phage.hash(state);
+        self.min_rpm.map(f64::to_bits).hash(state);
+        self.min_reads.hash(state);
+        self.min_contigs.hash(state);
+    }
+}
+impl TaxonCondition {
+    pub fn matches(&self, taxon: &Taxon, phage_list: &[String]) -> bool {
+        let records: Vec<(f64, u64, u64)> = taxon.evidence.records.iter()
+            .map(|record| (record.rpm, record.reads, record.contigs))
+            .collect();
+        self.holds(&taxon.name, taxon.lineage.get_domain().as_deref(), &records, phage_list)
+    }
+    /// Condition on the name, lineage domain and the rpm, reads and contigs of the profile records of a taxon
+    fn holds(&self, name: &str, domain: Option<&str>, records: &[(f64, u64, u64)], phage_list: &[String]) -> bool {
+        if !self.names.is_empty() && !self.names.iter().any(|n| n == name) {
+            return false
+        }
+        if !self.domains.is_empty() && !domain.is_some_and(|domain| self.domains.iter().any(|d| d == domain)) {
+            return false
+        }
+        if let Some(phage) = self.phage {
+            let is_phage = domain == Some("Viruses") && (
+                phage_list.iter().any(|p| p == name) || name.to_lowercase().contains("phage")
+            );
+            if is_phage != phage {
+                return false
+            }
+        }
+        if self.min_rpm.is_some_and(|min_rpm| !records.iter().any(|(rpm, _, _)| *rpm >= min_rpm)) {
+            return false
+        }
+        if self.min_reads.is_some_and(|min_reads| !records.iter().any(|(_, reads, _)| *reads >= min_reads)) {
+            return false
+        }
+        if self.min_contigs.is_some_and(|min_contigs| !records.iter().any(|(_, _, contigs)| *contigs >= min_contigs)) {
+            return false
+        }
+        true
+    }
+}
+
+/// Deterministic decision on the node data evaluated without a model
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, Hash, PartialEq)]
+pub struct NodeRule {
+    /// Rule name recorded in the node memory when the rule fires
+    pub name: String,
+    #[serde(rename = "match")]
+    pub quantifier: RuleMatch,
+    #[serde(default)]
+    pub condition: TaxonCondition
+}
+impl NodeRule {
+    pub fn evaluate(&self, taxa: &[Taxon], phage_list: &[String]) -> bool {
+        self.quantify(taxa.iter().map(|taxon| self.condition.matches(taxon, phage_list)))
+    }
+    /// Rule fires for the condition matches of the taxa
+    fn quantify(&self, mut matches: impl Iterator<Item = bool>) -> bool {
+        match self.quantifier {
+            RuleMatch::Any => matches.any(|m| m),
+            RuleMatch::All => matches.next().is_some_and(|first| first && matches.all(|m| m)),
+            RuleMatch::None => !matches.any(|m| m)
+        }
+    }
+}
+
+/// Candidate label and taxa of a node input with the memory of the earlier node for memory inputs
+pub type NodeInput = (CandidateLabel, Vec<Taxon>, Option<DiagnosticMemory>);
+
+/// Memory inputs with data have conflicting decisions - decisions of memories 
+/// without data are defaults and not considered
+pub fn conflicting_inputs<T>(inputs: &[(CandidateLabel, Vec<T>, Option<DiagnosticMemory>)]) -> bool {
+    let decisions: Vec<bool> = inputs.iter()
+        .filter(|(_, taxa, _)| !taxa.is_empty())
+        .filter_map(|(_, _, memory)| memory.as_ref().and_then(|memory| memory.result))
+        .collect();
+    decisions.contains(&true) && decisions.contains(&false)
+}
+
+/// Memory of the only input with data if that input is a memory input
+pub fn single_memory_input<T>(inputs: &[(CandidateLabel, Vec<T>, Option<DiagnosticMemory>)]) -> Option<&DiagnosticMemory> {
+    let mut with_data = inputs.iter().filter(|(_, taxa, _)| !taxa.is_empty());
+    match (with_data.next(), with_data.next()) {
+        (Some((_, _, memory)), None) => memory.as_ref(),
+        _ => None
+    }
+}
+
+/// Memory of an earlier node included in the node data
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, Hash, PartialEq)]
+pub struct MemoryInput {
+    /// Label or check type of the earlier node
+    pub node: String,
+    pub label: CandidateLabel,
+    /// Only include the memory if the node decision was positive
+    #[serde(default)]
+    pub positive: bool
+}
+
+/// Declarative specification of the data, prompt and answer parsing of a node 
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, Hash, PartialEq)]
+pub struct NodeSpec {
+    /// Prefetched data tiers combined into the node data
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tiers: Vec<DataTier>,
+    /// Earlier node memories included in the node data
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub memories: Vec<MemoryInput>,
+    /// Include the host copy-number evidence in the node data
+    #[serde(default)]
+    pub aneuploidy: bool,
+    /// Single heading for the combined tiers, otherwise each tier has its own heading
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<CandidateLabel>,
+    /// Tiers that must contain taxa for the node to be prompted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<DataTier>,
+    #[serde(default = "NodeSpec::default_parser")]
+    pub parser: NodeParser,
+    /// Prompt the node even if no data was retrieved
+    #[serde(default)]
+    pub prompt_empty: bool,
+    /// Reuse the memory of the only input memory with data instead of prompting
+    #[serde(default)]
+    pub reuse_single: bool,
+    /// Category branch taken if no data was retrieved for a category node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub empty_category: Option<String>,
+    /// Rules evaluated instead of prompting the model - the node decision 
+    /// is positive if any of the rules fires
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<NodeRule>,
+    /// Diagnosis assigned when the node is processed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnosis: Option<Diagnosis>,
+    /// Prompt layout template replacing the standard block order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Rendering of the candidate taxa - defaults to the renderer of the agent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub render: Option<TaxaRenderer>
+}
+impl Default for NodeSpec {
+    fn default() -> Self {
+        Self {
+            tiers: Vec::new(),
+            memories: Vec::new(),
+            aneuploidy: false,
+            label: None,
+            required: Vec::new(),
+            parser: Self::default_parser(),
+            prompt_empty: false,
+            reuse_single: false,
+            empty_category: None,
+            rules: Vec::new(),
+            diagnosis: None,
+            template: None,
+            render: None
+        }
+    }
+}
+impl NodeSpec {
+    fn default_parser() -> NodeParser {
+        NodeParser::Result
+    }
+    /// Memory of a node that is not prompted because no data was retrieved - result nodes 
+    /// decide no (no taxa detected) and category nodes take the empty category branch
+    pub fn empty_memory(&self, node: &TreeNode, data: Vec<Taxon>) -> Result<DiagnosticMemory, GptError> {
+        if self.parser == NodeParser::Category && self.empty_category.is_none() && node.next.is_none() {
+            return Err(GptError::TreeTransitionMissing(node.label.clone().unwrap_or("no_label".to_string()), "empty data".to_string()))
+        }
+        let memory = DiagnosticMemory::new(
+            DiagnosticNode::Generic, 
+            data, 
+            match self.parser {
+                NodeParser::Result => Some(false),
+                _ => None
+            }, 
+            None, 
+            None, 
+            None
+        );
+        Ok(memory.with_category(match self.parser {
+            NodeParser::Category => self.empty_category.clone(),
+            _ => None
+        }))
+    }
+    pub fn tiers(mut self, tiers: Vec<DataTier>) -> Self {
+        self.tiers = tiers;
+        self
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Eq, Hash, PartialEq)]
 pub struct TreeNode {
+    #[serde(skip_serializing_if = "Option::is_none")]
     label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     question: Option<Question>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     check: Option<DiagnosticNode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     true_node: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     false_node: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     next: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    branches: Option<BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     final_node: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spec: Option<NodeSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    generation: Option<NodeGeneration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    examples: Option<NodeExamples>,
 }
 
 
@@ -299,13 +1818,86 @@ impl Default for TreeNode {
             true_node: None,
             false_node: None,
             next: None,
+            branches: None,
             final_node: None,
+            spec: None,
+            generation: None,
+            examples: None,
         }
     }
 }
 
 impl TreeNode {
 
+    /// Digest of the authored question fields - prompt version of inline node prompts
+    pub fn question_digest(&self) -> String {
+        let text = self.question_fields().map(|(field, text)| format!("{field}:{text}")).join("\n");
+        format!("sha256:{}", &sha256_str(&text)[..12])
+    }
+    /// Question tasks, context, data and instructions (empty if not set)
+    pub fn question_fields(&self) -> [(&'static str, String); 4] {
+        let (tasks, context, data, instructions) = match &self.question {
+            Some(Question::Simple(prompt)) => (prompt.clone(), None, None, None),
+            Some(Question::Detailed { tasks, context, data, instructions }) => {
+                (tasks.clone(), context.clone(), data.clone(), instructions.clone())
+            },
+            None => (String::new(), None, None, None)
+        };
+        [
+            ("tasks", tasks),
+            ("context", context.unwrap_or_default()),
+            ("data", data.unwrap_or_default()),
+            ("instructions", instructions.unwrap_or_default())
+        ]
+    }
+
+    /// Node label with the check type on a second line
+    pub fn display_label(&self) -> String {
+        let label = self.label.clone().unwrap_or("no_label".to_string());
+        match self.check.as_ref().and_then(|check| serde_plain::to_string(check).ok()) {
+            Some(check) => format!("{label}\n({check})"),
+            None => label
+        }
+    }
+
+    /// Target node of a category branch
+    pub fn branch_target(&self, category: &str) -> Option<&String> {
+        self.branches.as_ref().and_then(|branches| {
+            branches.iter().find(|(key, _)| normalize_category(key) == normalize_category(category)).map(|(_, target)| target)
+        })
+    }
+
+    /// Rewrite the transition targets of the node
+    fn map_targets<F>(&mut self, f: F) -> Result<(), GptError>
+    where
+        F: Fn(&String) -> Result<String, GptError>
+    {
+        for target in [&mut self.true_node, &mut self.false_node, &mut self.next].into_iter().flatten() {
+            *target = f(target)?;
+        }
+        if let Some(branches) = self.branches.as_mut() {
+            for target in branches.values_mut() {
+                *target = f(target)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Model and sampling overrides of the node
+    pub fn generation(&self) -> Option<&NodeGeneration> {
+        self.generation.as_ref()
+    }
+
+    /// Few-shot examples referenced by the node
+    pub fn examples(&self) -> Option<&NodeExamples> {
+        self.examples.as_ref()
+    }
+
+    /// Node specification declared on the node or of its built-in check type
+    pub fn node_spec(&self) -> Option<NodeSpec> {
+        self.spec.clone().or_else(|| self.check.as_ref().and_then(DiagnosticNode::spec))
+    }
+
     /// Set a simple prompt
     pub fn with_prompt<S: Into<String>>(mut self, prompt: S) -> Self {
         let p = prompt.into();
@@ -335,6 +1927,29 @@ impl TreeNode {
         Ok(self)
     }
 
+    /// Render the question templates - context and data blocks of the 
+    /// question default to the context and data of the prompt variables
+    pub fn render_question(&self, variables: &PromptVariables) -> Result<Question, GptError> {
+        let (tasks, data, context, instructions) = match self.question.as_ref().ok_or(GptError::TreeNodeQuestionMissing)? {
+            Question::Simple(p) => (p, None, None, None),
+            Question::Detailed { tasks, data, context, instructions } => {
+                (tasks, data.as_ref(), context.as_ref(), instructions.as_ref())
+            }
+        };
+        Ok(Question::Detailed {
+            tasks: variables.render(tasks)?,
+            context: Some(match context {
+                Some(context) => variables.render(context)?,
+                None => variables.context.clone()
+            }),
+            data: Some(match data {
+                Some(data) => variables.render(data)?,
+                None => variables.data.clone()
+            }),
+            instructions: instructions.map(|instructions| variables.render(instructions)).transpose()?
+        })
+    }
+
     /// Add or replace the “Context:” block
     pub fn with_context<S: Into<String>>(mut self, context: S) -> Result<Self, GptError> {
         let context = context.into();
@@ -453,6 +2068,27 @@ impl TreeNode {
         self.label = Some(lbl.into());
         self
     }
+
+    pub fn with_spec(mut self, spec: NodeSpec) -> Self {
+        self.spec = Some(spec);
+        self
+    }
+    pub fn with_generation(mut self, generation: NodeGeneration) -> Self {
+        self.generation = Some(generation);
+        self
+    }
+    pub fn with_examples(mut self, examples: NodeExamples) -> Self {
+        self.examples = Some(examples);
+        self
+    }
+
+    /// Add a category branch of a categorical node
+    pub fn branch<S: Into<String>>(mut self, category: &str, tgt: S) -> Self {
+        self.branches
+            .get_or_insert_with(BTreeMap::new)
+            .insert(normalize_category(category), tgt.into());
+        self
+    }
 }
 
 
@@ -476,6 +2112,11 @@ impl TreeEdge {
             action: TreeAction::False
         }
     }
+    pub fn category(category: &str) -> Self {
+        Self {
+            action: TreeAction::Category(category.to_string())
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -483,7 +2124,18 @@ impl TreeEdge {
 pub enum TreeAction {
     Next,
     True,
-    False
+    False,
+    Category(String)
+}
+impl std::fmt::Display for TreeAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TreeAction::Next => write!(f, "next"),
+            TreeAction::True => write!(f, "true"),
+            TreeAction::False => write!(f, "false"),
+            TreeAction::Category(category) => write!(f, "{category}")
+        }
+    }
 }
 
 //
@@ -504,7 +2156,7 @@ pub struct EvaluationDetails {
 // === Final Diagnostic Result Structure ===
 //
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
 pub enum Diagnosis {
     Infectious,
     InfectiousReview,
@@ -528,19 +2180,102 @@ impl std::fmt::Display for Diagnosis {
     }
 }
 
+/// Uncertainty signals raised while processing the decision tree
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewReason {
+    /// Decision could not be extracted within the maximum number of repeats
+    ExtractionFailed,
+    /// No pathogen tag in the answer of a pathogen node
+    PathogenMissing,
+    /// Pathogen tag does not match any of the candidate taxa
+    PathogenNotInCandidates,
+    /// Memory inputs of a node have conflicting decisions
+    ConflictingTiers
+}
+
+/// Outcome of an uncertainty signal applied to the diagnosis
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewOutcome {
+    /// Keep the diagnosis
+    #[default]
+    Keep,
+    /// Infectious or non-infectious diagnosis to its review diagnosis
+    Review,
+    InfectiousReview,
+    NonInfectiousReview,
+}
+impl ReviewOutcome {
+    pub fn apply(&self, diagnosis: &Diagnosis) -> Diagnosis {
+        match (self, diagnosis) {
+            (ReviewOutcome::Review, Diagnosis::Infectious) => Diagnosis::InfectiousReview,
+            (ReviewOutcome::Review, Diagnosis::NonInfectious) => Diagnosis::NonInfectiousReview,
+            (ReviewOutcome::InfectiousReview, _) => Diagnosis::InfectiousReview,
+            (ReviewOutcome::NonInfectiousReview, _) => Diagnosis::NonInfectiousReview,
+            _ => diagnosis.clone()
+        }
+    }
+}
+
+/// Review outcomes of the uncertainty signals, the first signal with 
+/// an outcome other than keep determines the review reason - all signals 
+/// keep the diagnosis unless the tree opts in to a review outcome
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReviewPolicy {
+    #[serde(default)]
+    pub extraction_failed: ReviewOutcome,
+    #[serde(default)]
+    pub pathogen_missing: ReviewOutcome,
+    #[serde(default)]
+    pub pathogen_not_in_candidates: ReviewOutcome,
+    #[serde(default)]
+    pub conflicting_tiers: ReviewOutcome,
+}
+impl ReviewPolicy {
+    pub fn outcome(&self, reason: ReviewReason) -> ReviewOutcome {
+        match reason {
+            ReviewReason::ExtractionFailed => self.extraction_failed,
+            ReviewReason::PathogenMissing => self.pathogen_missing,
+            ReviewReason::PathogenNotInCandidates => self.pathogen_not_in_candidates,
+            ReviewReason::ConflictingTiers => self.conflicting_tiers
+        }
+    }
+    /// Apply the first signal with a review outcome that changes the diagnosis 
+    /// or flags it for review - signals without effect record no review reason, 
+    /// except a failed extraction which is recorded for the unknown diagnosis
+    pub fn apply(&self, result: &mut DiagnosticResult, signals: &[ReviewReason]) {
+        for reason in signals {
+            let diagnosis = self.outcome(*reason).apply(&result.diagnosis);
+            let flagged = matches!(diagnosis, Diagnosis::InfectiousReview | Diagnosis::NonInfectiousReview);
+            if self.outcome(*reason) != ReviewOutcome::Keep && (diagnosis != result.diagnosis || flagged) {
+                result.diagnosis = diagnosis;
+                result.review_reason = Some(*reason);
+                return
+            }
+        }
+        if result.diagnosis == Diagnosis::Unknown && signals.contains(&ReviewReason::ExtractionFailed) {
+            result.review_reason = Some(ReviewReason::ExtractionFailed);
+        }
+    }
+}
+
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DiagnosticResult {
     pub diagnosis: Diagnosis,
     pub candidates: Vec<String>,
     pub pathogen: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review_reason: Option<ReviewReason>,
 }
 impl DiagnosticResult {
     pub fn non_infectious() -> Self {
         Self {
             diagnosis: Diagnosis::NonInfectious,
             candidates: vec![],
-            pathogen: None
+            pathogen: None,
+            review_reason: None
         }
     }
     pub fn to_json(&self, path: &Path) -> Result<(), GptError> {
@@ -604,21 +2339,52 @@ impl GpuBenchmark {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiagnosticMemory {
     pub node: DiagnosticNode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
     pub data: Vec<Taxon>,
     pub result: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
     pub prompt: Option<String>,
     pub thoughts: Option<String>,
     pub answer: Option<String>
 }
 impl DiagnosticMemory {
     pub fn new(node: DiagnosticNode, data: Vec<Taxon>, result: Option<bool>, prompt: Option<String>, thoughts: Option<String>, answer: Option<String>) -> Self {
-        Self { node, data, result, prompt, thoughts, answer }
+        Self { node, label: None, data, result, category: None, rule: None, prompt, thoughts, answer }
+    }
+    pub fn with_rule(mut self, rule: Option<String>) -> Self {
+        self.rule = rule;
+        self
+    }
+    pub fn with_category(mut self, category: Option<String>) -> Self {
+        self.category = category;
+        self
+    }
+    pub fn labelled(mut self, label: &str, node: DiagnosticNode) -> Self {
+        self.label = Some(label.to_string());
+        self.node = node;
+        self
+    }
+    /// Transition of the node decision - none if no decision was extracted
+    pub fn action(&self) -> Option<TreeAction> {
+        match (&self.category, self.result) {
+            (Some(category), _) => Some(TreeAction::Category(category.clone())),
+            (None, Some(true)) => Some(TreeAction::True),
+            (None, Some(false)) => Some(TreeAction::False),
+            (None, None) => None
+        }
     }
     pub fn non_infectious(node: DiagnosticNode) -> Self {
         Self {
             node,
+            label: None,
             data: vec![],
             result:  Some(false),
+            category: None,
+            rule: None,
             prompt: None,
             thoughts: None,
             answer: None
@@ -630,7 +2396,17 @@ impl DiagnosticMemory {
 pub struct AgentState {
     pub memory: Vec<DiagnosticMemory>,
     pub post_filter_config: Option<PostFilterConfig>,
-    pub repeat: HashMap<DiagnosticNode, usize>,
+    pub repeat: HashMap<String, usize>,
+    /// Versions of the prompts used in the run by prompt identifier - library 
+    /// prompts by version or `builtin` and the node prompts of the tree as 
+    /// `node.{label}` by digest, which covers inline prompts of tree files
+    #[serde(default)]
+    pub prompts: BTreeMap<String, String>,
+    /// Final diagnosis of the run after review
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnosis: Option<Diagnosis>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review_reason: Option<ReviewReason>,
 }
 
 impl AgentState {
@@ -638,7 +2414,10 @@ impl AgentState {
         AgentState {
             memory: Vec::new(),
             post_filter_config: None,
-            repeat: HashMap::new()
+            repeat: HashMap::new(),
+            prompts: BTreeMap::new(),
+            diagnosis: None,
+            review_reason: None
         }
     }
 
@@ -649,12 +2428,52 @@ impl AgentState {
     pub fn retrieve(&self, node: DiagnosticNode) -> Option<&DiagnosticMemory> {
         self.memory.iter().find(|mem| mem.node == node)
     }
+    /// Decisions of the memorized nodes by node label or check type
+    pub fn answers(&self) -> BTreeMap<String, PriorAnswer> {
+        self.memory.iter().map(|mem| (
+            mem.label.clone().unwrap_or(serde_plain::to_string(&mem.node).unwrap_or_default()),
+            PriorAnswer {
+                result: mem.result,
+                category: mem.category.clone(),
+                rule: mem.rule.clone(),
+                answer: mem.answer.clone(),
+                taxa: mem.data.len()
+            }
+        )).collect()
+    }
+    /// Most recent memory of a node by label or check type
+    pub fn recall(&self, node: &str) -> Option<&DiagnosticMemory> {
+        self.memory.iter().rev().find(|mem| {
+            mem.label.as_deref() == Some(node) || serde_plain::to_string(&mem.node).is_ok_and(|check| check == node)
+        })
+    }
+    /// Inputs of a node from the taxa of its data tiers and the recalled memories of earlier nodes
+    pub fn node_inputs(&self, spec: &NodeSpec, tiers: &[(DataTier, Vec<Taxon>)]) -> Vec<NodeInput> {
+        let mut inputs: Vec<NodeInput> = match &spec.label {
+            Some(label) => vec![(label.clone(), tiers.iter().flat_map(|(_, taxa)| taxa.clone()).collect(), None)],
+            None => tiers.iter().map(|(tier, taxa)| (tier.label(), taxa.clone(), None)).collect()
+        };
+        for input in &spec.memories {
+            match self.recall(&input.node) {
+                Some(memory) if !input.positive || memory.result == Some(true) => {
+                    inputs.push((input.label.clone(), memory.data.clone(), Some(memory.clone())));
+                },
+                _ => continue
+            }
+        }
+        inputs
+    }
     pub fn to_json(&mut self, path: &Path) -> Result<(), GptError> {
         let agent_state = serde_json::to_string_pretty(self).map_err(|err| GptError::SerdeJsonError(err))?;
         let mut writer = BufWriter::new(File::create(path)?);
         write!(writer, "{agent_state}")?;
         Ok(())
     }
+    pub fn from_json<P: AsRef<Path>>(path: P) -> Result<Self, GptError> {
+        let data = std::fs::read_to_string(path)?;
+        let state = serde_json::from_str::<AgentState>(&data)?;
+        Ok(state)
+    }
 }
 
 //
@@ -699,7 +2518,7 @@ impl ThresholdTaxa {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, clap::ValueEnum)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, clap::ValueEnum)]
 pub enum GptModel {
     #[serde(rename="o4-mini")]
     O4Mini,
@@ -751,13 +2570,71 @@ impl GptModel {
     }
 }
 
+/// Local generator model or remote model by name
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub enum NodeModel {
+    Local(GeneratorModel),
+    Remote(GptModel)
+}
+impl TryFrom<String> for NodeModel {
+    type Error = String;
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        if let Ok(model) = <GeneratorModel as clap::ValueEnum>::from_str(&name, true) {
+            return Ok(NodeModel::Local(model))
+        }
+        serde_plain::from_str::<GptModel>(&name)
+            .map(NodeModel::Remote)
+            .map_err(|_| format!("unknown local or remote model: {name}"))
+    }
+}
+impl From<NodeModel> for String {
+    fn from(model: NodeModel) -> Self {
+        match model {
+            NodeModel::Local(model) => clap::ValueEnum::to_possible_value(&model)
+                .map(|value| value.get_name().to_string())
+                .unwrap_or_default(),
+            NodeModel::Remote(model) => String::from(&model)
+        }
+    }
+}
+impl std::fmt::Display for NodeModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", String::from(*self))
+    }
+}
+
+/// Model and sampling overrides of a node - unset values use the agent defaults
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct NodeGeneration {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<NodeModel>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_len: Option<usize>,
+    /// Thinking mode for models that support it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<bool>
+}
+impl Eq for NodeGeneration {}
+impl Hash for NodeGeneration {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.model.hash(state);
+        self.temperature.map(f64::to_bits).hash(state);
+        self.sample_len.hash(state);
+        self.thinking.hash(state);
+    }
+}
+
 impl From<&GptModel> for String {
     fn from(model: &GptModel) -> Self {
         serde_plain::to_string(model).expect("GptModel serialization failed")
     }
 }
 
-pub type TreeNodes = HashMap<String, TreeNode>;
+// Ordered by label for stable serialization of decision tree files
+pub type TreeNodes = BTreeMap<String, TreeNode>;
 
 pub trait TreeNodeReader {
     fn from_vec(v: Vec<TreeNode>) -> Result<TreeNodes, GptError>;
@@ -776,7 +2653,7 @@ impl TreeNodeReader for TreeNodes {
                 node
             ))
         }
-        Ok(HashMap::from_iter(label_nodes))
+        Ok(BTreeMap::from_iter(label_nodes))
     }
 }
 
@@ -800,6 +2677,7 @@ pub enum NodeTask {
     DiagnoseDefaultBelowTarget,
 
     DiagnoseInfectious,
+    DiagnoseTumor,
 }
 impl Into<String> for NodeTask {
     fn into(self) -> String {
@@ -844,15 +2722,32 @@ impl Into<String> for NodeTask {
                 - If the species is a human pathogen, consider selecting it as most likely pathogen.
                 - If a virus is detected, strongly consider selecting it as most likely pathogen.
             "),
+            NodeTask::DiagnoseTumor => dedent(r"
+                You have not made an infectious diagnosis for this sample. 
+
+                - Determine if the host copy-number profile [Data] suggests a malignancy (tumor) in the context of the provided sample type [Sample] and clinical information [Clinical].
+                - Consider that chromosomal aneuploidy and focal copy-number alterations in the human reads of the sample are indicative of tumor-derived DNA, while a flat profile is expected for non-neoplastic samples.
+                - Consider that low human read depth and noisy segments can produce spurious copy-number alterations.
+            "),
         }
     }
 }
 
 
+impl BuiltinPrompt for NodeTask {
+    fn prompt_id(&self) -> String {
+        prompt_id("task", self)
+    }
+    fn builtin_text(&self) -> String {
+        self.clone().into()
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, clap::ValueEnum)]
 pub enum NodeInstruction {
     DiagnoseDefault,
     DiagnoseInfectious,
+    DiagnoseTumor,
 }
 
 impl Into<String> for NodeInstruction {
@@ -872,32 +2767,724 @@ impl Into<String> for NodeInstruction {
                 Example: <pathogen>Rodorendens figura</pathogen>
 
                 Your output:
+            "),
+            NodeInstruction::DiagnoseTumor => dedent(r"
+                1.  Output your determination inside <result></result> tags (XML).
+                1a. Output 'yes' in <result></result> tags (<result>yes</result>) if the data suggests a malignancy. 
+                1b. Output 'no' in <result></result> tags (<result>no</result>) if the data does not suggest a malignancy. 
             ")
         }
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct DecisionTree {
-    pub name: String,
-    pub version: String,
-    pub description: String,
-    pub max_repeats: usize,
-    pub nodes: TreeNodes
-}
-impl DecisionTree {
-    pub fn new(name: &str, version: &str, description: &str, nodes: &str) -> Result<Self, GptError> {
-        Ok(
-            Self {
-                name: name.to_string(),
-                version: version.to_string(),
-                description: description.to_string(),
-                max_repeats: 3,
-                nodes: TreeNodes::from_str(nodes)?
+impl BuiltinPrompt for NodeInstruction {
+    fn prompt_id(&self) -> String {
+        prompt_id("instruction", self)
+    }
+    fn builtin_text(&self) -> String {
+        self.clone().into()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TreeIssueSeverity {
+    Error,
+    Warning
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TreeIssueKind {
+    RootUnresolved,
+    DanglingTarget,
+    AmbiguousTransition,
+    MissingBranch,
+    UnreachableNode,
+    NonTerminatingCycle,
+    NonFinalLeaf,
+    CheckTypeMissing,
+    SpecMissing,
+    QuestionMissing,
+    InstructionTagMissing,
+    ModelUnsupported
+}
+impl TreeIssueKind {
+    pub fn severity(&self) -> TreeIssueSeverity {
+        match self {
+            TreeIssueKind::UnreachableNode => TreeIssueSeverity::Warning,
+            _ => TreeIssueSeverity::Error
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TreeIssue {
+    pub kind: TreeIssueKind,
+    pub severity: TreeIssueSeverity,
+    pub node: Option<String>,
+    pub message: String
+}
+impl TreeIssue {
+    pub fn new(kind: TreeIssueKind, node: Option<&str>, message: String) -> Self {
+        Self {
+            kind,
+            severity: kind.severity(),
+            node: node.map(String::from),
+            message
+        }
+    }
+    pub fn is_error(&self) -> bool {
+        self.severity == TreeIssueSeverity::Error
+    }
+}
+
+/// Named sub-tree included into a decision tree - sub-tree nodes are 
+/// namespaced as `{name}.{label}` and transitions of the including tree 
+/// to the include name enter the sub-tree root
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TreeInclude {
+    pub name: String,
+    /// Sub-tree file relative to the including tree file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// Built-in sub-tree if no file is provided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tree: Option<TreeConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<TaskConfig>,
+    /// Targets in the including tree of the sub-tree exits (`@exit` transitions)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub exits: BTreeMap<String, String>
+}
+impl TreeInclude {
+    /// Namespaced label of a sub-tree node
+    pub fn namespace(&self, label: &str) -> String {
+        format!("{}.{}", self.name, label)
+    }
+    fn load(&self, base: &Path, depth: usize, prompts: &PromptLibrary) -> Result<DecisionTree, GptError> {
+        match (&self.file, &self.tree) {
+            (Some(file), _) => DecisionTree::read(base.join(file), depth + 1, prompts),
+            (None, Some(tree)) => DecisionTree::from_prompts(
+                tree.clone(), 
+                self.task.clone().unwrap_or(TaskConfig::Default),
+                prompts
+            ),
+            (None, None) => Err(GptError::TreeIncludeSourceMissing(self.name.clone()))
+        }
+    }
+}
+
+/// Maximum depth of nested sub-tree includes
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DecisionTree {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub max_repeats: usize,
+    /// Starting node - inferred from the transitions if not declared
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,
+    /// Review outcomes of uncertainty signals
+    #[serde(default)]
+    pub review: ReviewPolicy,
+    /// Sub-trees expanded into the nodes when the tree file is read
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<TreeInclude>,
+    pub nodes: TreeNodes
+}
+impl DecisionTree {
+    pub fn new(name: &str, version: &str, description: &str, nodes: &str) -> Result<Self, GptError> {
+        Ok(
+            Self {
+                name: name.to_string(),
+                version: version.to_string(),
+                description: description.to_string(),
+                max_repeats: 3,
+                root: None,
+                review: ReviewPolicy::default(),
+                includes: Vec::new(),
+                nodes: TreeNodes::from_str(nodes)?
+            }
+        )
+    }
+    pub fn from_config(tree_config: TreeConfig, task_config: TaskConfig) -> Result<Self, GptError> {
+        Self::from_prompts(tree_config, task_config, &PromptLibrary::default())
+    }
+    /// Built-in decision tree with the node prompts resolved against a prompt library
+    pub fn from_prompts(tree_config: TreeConfig, task_config: TaskConfig, prompts: &PromptLibrary) -> Result<Self, GptError> {
+        match tree_config {
+            TreeConfig::Tiered => DecisionTree::tiered(task_config, prompts),
+            TreeConfig::TieredThreshold => DecisionTree::tiered_threshold(task_config, prompts),
+            TreeConfig::TieredAneuploidy => DecisionTree::tiered_aneuploidy(task_config, prompts),
+            TreeConfig::SingleNode => DecisionTree::single_node(task_config, prompts)
+        }
+    }
+    pub fn from_json<P: AsRef<Path>>(path: P) -> Result<Self, GptError> {
+        Self::read_json(path.as_ref(), 0, &PromptLibrary::default())
+    }
+    fn read_json(path: &Path, depth: usize, prompts: &PromptLibrary) -> Result<Self, GptError> {
+        let data = std::fs::read_to_string(path)?;
+        let tree = serde_json::from_str::<DecisionTree>(&data)?;
+        tree.expand_includes(path, depth, prompts)
+    }
+    pub fn to_json(&self, path: &Path) -> Result<(), GptError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write!(&mut writer, "{}", serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+    pub fn from_yaml<P: AsRef<Path>>(path: P) -> Result<Self, GptError> {
+        Self::read_yaml(path.as_ref(), 0, &PromptLibrary::default())
+    }
+    fn read_yaml(path: &Path, depth: usize, prompts: &PromptLibrary) -> Result<Self, GptError> {
+        let data = std::fs::read_to_string(path)?;
+        let tree = serde_yaml::from_str::<DecisionTree>(&data)?;
+        tree.expand_includes(path, depth, prompts)
+    }
+    pub fn to_yaml(&self, path: &Path) -> Result<(), GptError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write!(&mut writer, "{}", serde_yaml::to_string(self)?)?;
+        Ok(())
+    }
+    /// Read a decision tree from a YAML (.yaml, .yml) or JSON file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, GptError> {
+        Self::read(path, 0, &PromptLibrary::default())
+    }
+    /// Read a decision tree file with the prompts of built-in sub-tree includes 
+    /// resolved against a prompt library - node prompts of the file are inline
+    pub fn from_file_with_prompts<P: AsRef<Path>>(path: P, prompts: &PromptLibrary) -> Result<Self, GptError> {
+        Self::read(path, 0, prompts)
+    }
+    /// Decision tree file if provided, otherwise the built-in decision tree
+    pub fn from_file_or_config(file: Option<&Path>, tree_config: TreeConfig, task_config: TaskConfig, prompts: &PromptLibrary) -> Result<Self, GptError> {
+        match file {
+            Some(path) => Self::from_file_with_prompts(path, prompts),
+            None => Self::from_prompts(tree_config, task_config, prompts)
+        }
+    }
+    fn read<P: AsRef<Path>>(path: P, depth: usize, prompts: &PromptLibrary) -> Result<Self, GptError> {
+        let path = path.as_ref();
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(GptError::TreeIncludeDepth(path.display().to_string()))
+        }
+        if is_yaml(path) {
+            Self::read_yaml(path, depth, prompts)
+        } else {
+            Self::read_json(path, depth, prompts)
+        }
+    }
+    /// Expand the sub-tree includes into namespaced nodes, wiring the 
+    /// sub-tree exits to their targets and include names to sub-tree roots
+    fn expand_includes(mut self, path: &Path, depth: usize, prompts: &PromptLibrary) -> Result<Self, GptError> {
+        if self.includes.is_empty() {
+            return Ok(self)
+        }
+        let base = path.parent().unwrap_or(Path::new("."));
+
+        // Include names resolve to the namespaced root of the sub-tree
+        let mut entries = HashMap::new();
+        let mut subtrees = Vec::new();
+        for include in &self.includes {
+            if entries.contains_key(&include.name) || self.nodes.contains_key(&include.name) {
+                return Err(GptError::TreeNodeDuplicate(include.name.clone()))
+            }
+            let subtree = include.load(base, depth, prompts)?;
+            entries.insert(include.name.clone(), include.namespace(&subtree.root_label()?));
+            subtrees.push((include, subtree));
+        }
+        let resolve = |target: &String| entries.get(target).cloned().unwrap_or(target.clone());
+
+        for node in self.nodes.values_mut() {
+            node.map_targets(|target| Ok(resolve(target)))?;
+        }
+        self.root = self.root.as_ref().map(resolve);
+
+        for (include, subtree) in subtrees {
+            let labels: HashSet<String> = subtree.nodes.keys().cloned().collect();
+            for (label, mut node) in subtree.nodes {
+                node.map_targets(|target| match target.strip_prefix('@') {
+                    Some(exit) => include.exits
+                        .get(exit)
+                        .map(resolve)
+                        .ok_or(GptError::TreeIncludeExitMissing(include.name.clone(), exit.to_string())),
+                    None => Ok(include.namespace(target))
+                })?;
+                if let Some(spec) = node.spec.as_mut() {
+                    for memory in spec.memories.iter_mut() {
+                        if labels.contains(&memory.node) {
+                            memory.node = include.namespace(&memory.node);
+                        }
+                    }
+                }
+                let label = include.namespace(&label);
+                node.label = Some(label.clone());
+                
+                if self.nodes.insert(label.clone(), node).is_some() {
+                    return Err(GptError::TreeNodeDuplicate(label))
+                }
+            }
+        }
+        self.includes.clear();
+
+        Ok(self)
+    }
+    /// Write the decision tree to a file in the format of the file extension
+    pub fn to_file(&self, path: &Path) -> Result<(), GptError> {
+        self.to_format(path, TreeFormat::from_path(path))
+    }
+    pub fn to_format(&self, path: &Path, format: TreeFormat) -> Result<(), GptError> {
+        match format {
+            TreeFormat::Json => self.to_json(path),
+            TreeFormat::Yaml => self.to_yaml(path),
+            TreeFormat::Dot | TreeFormat::Mermaid => {
+                let graph = match format {
+                    TreeFormat::Dot => self.to_dot()?,
+                    _ => self.to_mermaid()?
+                };
+                let mut writer = BufWriter::new(File::create(path)?);
+                write!(&mut writer, "{graph}")?;
+                Ok(())
+            }
+        }
+    }
+    /// Graphviz DOT graph with node shapes by node type and labelled transitions
+    pub fn to_dot(&self) -> Result<String, GptError> {
+        self.dot(None)
+    }
+    /// Mermaid flowchart with node shapes by node type and labelled transitions
+    pub fn to_mermaid(&self) -> Result<String, GptError> {
+        self.mermaid(None)
+    }
+    /// Graphviz DOT graph with the execution path of a sample highlighted
+    pub fn to_dot_overlay(&self, overlay: &PathOverlay) -> Result<String, GptError> {
+        self.dot(Some(overlay))
+    }
+    /// Mermaid flowchart with the execution path of a sample highlighted
+    pub fn to_mermaid_overlay(&self, overlay: &PathOverlay) -> Result<String, GptError> {
+        self.mermaid(Some(overlay))
+    }
+    fn dot(&self, overlay: Option<&PathOverlay>) -> Result<String, GptError> {
+        let graph = DiagnosticAgent::graph(self)?;
+        let root = self.root_label().ok();
+
+        let mut dot = format!("digraph \"{}\" {{\n", dot_escape(&self.name));
+        dot.push_str("    rankdir=TB;\n");
+        dot.push_str("    node [fontname=\"Helvetica\", fontsize=10];\n");
+        dot.push_str("    edge [fontname=\"Helvetica\", fontsize=9];\n\n");
+
+        for node in graph.node_weights() {
+            let label = node.label.clone().unwrap_or("no_label".to_string());
+            let shape = NodeShape::from_node(node);
+            let mut display = node.display_label();
+            let mut attributes = vec![format!("shape={}", shape.dot())];
+
+            if root.as_ref() == Some(&label) && !overlay.is_some_and(|overlay| overlay.nodes.contains_key(&label)) {
+                attributes.push("penwidth=2".to_string());
+            }
+            match overlay {
+                Some(overlay) => match overlay.nodes.get(&label) {
+                    Some(visit) => {
+                        display.push_str(&format!("\n{}", visit.annotation()));
+                        if let Some(diagnosis) = overlay.diagnosis_text(&label) {
+                            display.push_str(&format!("\n{diagnosis}"));
+                        }
+                        attributes.push("color=\"#c0392b\"".to_string());
+                        attributes.push("penwidth=2".to_string());
+                        attributes.push("style=filled".to_string());
+                        attributes.push(match overlay.is_last(&label) {
+                            true => "fillcolor=\"#f5b7b1\"".to_string(),
+                            false => "fillcolor=\"#fdedec\"".to_string()
+                        });
+                    },
+                    None => {
+                        attributes.push("color=\"#aaaaaa\"".to_string());
+                        attributes.push("fontcolor=\"#aaaaaa\"".to_string());
+                    }
+                },
+                None => if shape == NodeShape::Final {
+                    attributes.push("style=filled".to_string());
+                    attributes.push("fillcolor=\"#e8e8e8\"".to_string());
+                }
+            }
+            attributes.insert(0, format!("label=\"{}\"", dot_escape(&display)));
+            dot.push_str(&format!("    \"{}\" [{}];\n", dot_escape(&label), attributes.join(", ")));
+        }
+        dot.push('\n');
+
+        for edge in graph.edge_references() {
+            let source = graph[edge.source()].label.as_deref().unwrap_or("no_label");
+            let target = graph[edge.target()].label.as_deref().unwrap_or("no_label");
+            let style = match overlay {
+                Some(overlay) if overlay.traversed(source, target) => ", color=\"#c0392b\", penwidth=2",
+                Some(_) => ", color=\"#aaaaaa\", fontcolor=\"#aaaaaa\"",
+                None => ""
+            };
+            dot.push_str(&format!(
+                "    \"{}\" -> \"{}\" [label=\"{}\"{style}];\n",
+                dot_escape(source),
+                dot_escape(target),
+                dot_escape(&edge.weight().action.to_string())
+            ));
+        }
+        dot.push_str("}\n");
+
+        Ok(dot)
+    }
+    fn mermaid(&self, overlay: Option<&PathOverlay>) -> Result<String, GptError> {
+        let graph = DiagnosticAgent::graph(self)?;
+        let root = self.root_label().ok();
+
+        let ids = mermaid_ids(graph.node_weights().map(|node| node.label.as_deref().unwrap_or("no_label")));
+        let mermaid_id = |label: &str| ids.get(label).cloned().unwrap_or_default();
+
+        let mut mermaid = String::from("flowchart TD\n");
+
+        for node in graph.node_weights() {
+            let label = node.label.clone().unwrap_or("no_label".to_string());
+            let (open, close) = NodeShape::from_node(node).mermaid();
+            let mut display = node.display_label();
+            if let Some(visit) = overlay.and_then(|overlay| overlay.nodes.get(&label)) {
+                display.push_str(&format!("\n{}", visit.annotation()));
+            }
+            if let Some(diagnosis) = overlay.and_then(|overlay| overlay.diagnosis_text(&label)) {
+                display.push_str(&format!("\n{diagnosis}"));
+            }
+            mermaid.push_str(&format!(
+                "    {}{open}\"{}\"{close}\n", 
+                mermaid_id(&label), 
+                mermaid_escape(&display).replace('\n', "<br/>")
+            ));
+        }
+        let mut traversed = Vec::new();
+        for (i, edge) in graph.edge_references().enumerate() {
+            let source = graph[edge.source()].label.as_deref().unwrap_or("no_label");
+            let target = graph[edge.target()].label.as_deref().unwrap_or("no_label");
+            if overlay.is_some_and(|overlay| overlay.traversed(source, target)) {
+                traversed.push(i.to_string());
+            }
+            mermaid.push_str(&format!(
+                "    {} -->|{}| {}\n",
+                mermaid_id(source),
+                mermaid_escape(&edge.weight().action.to_string()),
+                mermaid_id(target)
+            ));
+        }
+        if let Some(root) = root {
+            mermaid.push_str(&format!("    style {} stroke-width:3px\n", mermaid_id(&root)));
+        }
+        if let Some(overlay) = overlay {
+            mermaid.push_str("    classDef visited fill:#fdedec,stroke:#c0392b,stroke-width:2px\n");
+            mermaid.push_str("    classDef diagnosis fill:#f5b7b1,stroke:#c0392b,stroke-width:2px\n");
+            for label in overlay.nodes.keys().filter(|label| ids.contains_key(*label)) {
+                let class = match overlay.is_last(label) {
+                    true => "diagnosis",
+                    false => "visited"
+                };
+                mermaid.push_str(&format!("    class {} {class}\n", mermaid_id(label)));
+            }
+            if !traversed.is_empty() {
+                mermaid.push_str(&format!("    linkStyle {} stroke:#c0392b,stroke-width:2px\n", traversed.join(",")));
+            }
+        }
+
+        Ok(mermaid)
+    }
+    /// Semantic changes from this tree to another version of the tree
+    pub fn diff(&self, other: &DecisionTree) -> Result<Vec<TreeChange>, GptError> {
+
+        let mut changes = Vec::new();
+
+        let fields = [
+            ("name", self.name.clone(), other.name.clone()),
+            ("version", self.version.clone(), other.version.clone()),
+            ("max_repeats", self.max_repeats.to_string(), other.max_repeats.to_string()),
+            ("root", self.root_label().unwrap_or_default(), other.root_label().unwrap_or_default()),
+            ("review", serde_json::to_string(&self.review)?, serde_json::to_string(&other.review)?),
+        ];
+        for (field, before, after) in fields {
+            if before != after {
+                changes.push(TreeChange::new(TreeChangeKind::TreeChanged, None, field, Some(before), Some(after)));
+            }
+        }
+        if self.description != other.description {
+            changes.push(TreeChange::tree_text(TreeChangeKind::TreeChanged, "description", &self.description, &other.description));
+        }
+
+        for label in self.nodes.keys().filter(|label| !other.nodes.contains_key(*label)) {
+            changes.push(TreeChange::new(TreeChangeKind::NodeRemoved, Some(label), "node", Some(label.clone()), None));
+        }
+        for label in other.nodes.keys().filter(|label| !self.nodes.contains_key(*label)) {
+            changes.push(TreeChange::new(TreeChangeKind::NodeAdded, Some(label), "node", None, Some(label.clone())));
+        }
+
+        for (label, before) in &self.nodes {
+            let Some(after) = other.nodes.get(label) else {
+                continue
+            };
+
+            let mut transitions = vec![
+                ("true_node".to_string(), before.true_node.clone(), after.true_node.clone()),
+                ("false_node".to_string(), before.false_node.clone(), after.false_node.clone()),
+                ("next".to_string(), before.next.clone(), after.next.clone()),
+            ];
+            let categories: std::collections::BTreeSet<&String> = before.branches.iter().flatten()
+                .chain(after.branches.iter().flatten())
+                .map(|(category, _)| category)
+                .collect();
+            for category in categories {
+                transitions.push((
+                    format!("branch '{category}'"), 
+                    before.branch_target(category).cloned(), 
+                    after.branch_target(category).cloned()
+                ));
+            }
+            for (field, target_before, target_after) in transitions {
+                if target_before != target_after {
+                    changes.push(TreeChange::new(TreeChangeKind::TransitionChanged, Some(label), &field, target_before, target_after));
+                }
+            }
+
+            if before.check != after.check {
+                let check = |node: &TreeNode| node.check.as_ref().and_then(|check| serde_plain::to_string(check).ok());
+                changes.push(TreeChange::new(TreeChangeKind::CheckChanged, Some(label), "check", check(before), check(after)));
+            }
+            if before.final_node.unwrap_or(false) != after.final_node.unwrap_or(false) {
+                changes.push(TreeChange::new(
+                    TreeChangeKind::FinalChanged, Some(label), "final_node", 
+                    Some(before.final_node.unwrap_or(false).to_string()), 
+                    Some(after.final_node.unwrap_or(false).to_string())
+                ));
+            }
+
+            let (spec_before, spec_after) = (before.node_spec(), after.node_spec());
+            if spec_before != spec_after {
+                let spec = |spec: Option<NodeSpec>| -> Result<String, GptError> {
+                    Ok(match spec { Some(spec) => serde_yaml::to_string(&spec)?, None => String::new() })
+                };
+                changes.push(TreeChange::text(TreeChangeKind::SpecChanged, label, "spec", &spec(spec_before)?, &spec(spec_after)?));
+            }
+            if before.generation() != after.generation() {
+                let generation = |node: &TreeNode| node.generation().map(serde_json::to_string).transpose();
+                changes.push(TreeChange::new(TreeChangeKind::GenerationChanged, Some(label), "generation", generation(before)?, generation(after)?));
+            }
+            if before.examples() != after.examples() {
+                let examples = |node: &TreeNode| node.examples().map(serde_json::to_string).transpose();
+                changes.push(TreeChange::new(TreeChangeKind::ExamplesChanged, Some(label), "examples", examples(before)?, examples(after)?));
+            }
+
+            let (question_before, question_after) = (before.question_fields(), after.question_fields());
+            for ((field, text_before), (_, text_after)) in question_before.into_iter().zip(question_after) {
+                if text_before != text_after {
+                    changes.push(TreeChange::text(TreeChangeKind::QuestionChanged, label, field, &text_before, &text_after));
+                }
+            }
+        }
+
+        Ok(changes)
+    }
+    /// Declared root node or the unique node without incoming transitions
+    pub fn root_label(&self) -> Result<String, GptError> {
+        if let Some(root) = &self.root {
+            return match self.nodes.contains_key(root) {
+                true => Ok(root.clone()),
+                false => Err(GptError::TreeRootUndefined(root.clone()))
+            }
+        }
+
+        let graph = DiagnosticAgent::graph(self)?;
+
+        let roots: Vec<String> = graph
+            .node_indices()
+            .filter(|&n| graph.neighbors_directed(n, Direction::Incoming).next().is_none())
+            .filter_map(|n| graph[n].label.clone())
+            .collect();
+
+        match roots.as_slice() {
+            [] => Err(GptError::TreeRootMissing),
+            [root] => Ok(root.clone()),
+            _ => Err(GptError::TreeRootAmbiguous(roots.join(", ")))
+        }
+    }
+    /// Static checks of the node transitions, check types and prompt 
+    /// instructions that would otherwise only fail at runtime
+    pub fn validate(&self) -> Result<Vec<TreeIssue>, GptError> {
+
+        let mut issues = Vec::new();
+
+        let root = match self.root_label() {
+            Ok(root) => Some(root),
+            Err(err) => {
+                issues.push(TreeIssue::new(TreeIssueKind::RootUnresolved, None, err.to_string()));
+                None
+            }
+        };
+
+        for (label, node) in &self.nodes {
+
+            let mut targets = vec![
+                ("true_node".to_string(), &node.true_node), 
+                ("false_node".to_string(), &node.false_node), 
+                ("next".to_string(), &node.next)
+            ];
+            let categories: Vec<(String, Option<String>)> = node.branches.iter().flatten().map(|(category, target)| {
+                (format!("branch '{category}'"), Some(target.clone()))
+            }).collect();
+            targets.extend(categories.iter().map(|(field, target)| (field.clone(), target)));
+
+            for (field, target) in targets {
+                if let Some(target) = target.as_ref().filter(|target| !self.nodes.contains_key(*target)) {
+                    issues.push(TreeIssue::new(
+                        TreeIssueKind::DanglingTarget, Some(label), format!("{field} target '{target}' is not defined")
+                    ));
+                }
+            }
+
+            let categorical = node.branches.as_ref().is_some_and(|branches| !branches.is_empty());
+            let branches = node.true_node.is_some() || node.false_node.is_some() || categorical;
+
+            if node.next.is_some() && branches {
+                issues.push(TreeIssue::new(
+                    TreeIssueKind::AmbiguousTransition, Some(label), "node has both next and branch targets".to_string()
+                ));
+            }
+            if categorical && (node.true_node.is_some() || node.false_node.is_some()) {
+                issues.push(TreeIssue::new(
+                    TreeIssueKind::AmbiguousTransition, Some(label), "node has both category and true/false branch targets".to_string()
+                ));
+            }
+            let parser = node.node_spec().map(|spec| spec.parser);
+            if categorical != (parser == Some(NodeParser::Category)) && node.next.is_none() {
+                issues.push(TreeIssue::new(
+                    TreeIssueKind::MissingBranch, Some(label), match categorical {
+                        true => "category branches require a category parser".to_string(),
+                        false => "category parser requires category branches".to_string()
+                    }
+                ));
+            }
+            if let Some(spec) = node.node_spec().filter(|spec| spec.parser == NodeParser::Category && spec.rules.is_empty() && node.next.is_none()) {
+                match &spec.empty_category {
+                    None if !spec.prompt_empty => issues.push(TreeIssue::new(
+                        TreeIssueKind::MissingBranch, Some(label), "category node requires an empty_category for samples without data".to_string()
+                    )),
+                    Some(category) if node.branch_target(category).is_none() => issues.push(TreeIssue::new(
+                        TreeIssueKind::MissingBranch, Some(label), format!("empty_category has no branch target: {category}")
+                    )),
+                    _ => {}
+                }
+            }
+            let unsupported = |model: &NodeModel| matches!(model, NodeModel::Remote(model) if !model.is_openai());
+            if let Some(NodeModel::Remote(model)) = node.generation().and_then(|generation| generation.model).filter(unsupported) {
+                issues.push(TreeIssue::new(
+                    TreeIssueKind::ModelUnsupported, Some(label), format!("remote model is not supported for node generation: {}", String::from(&model))
+                ));
+            }
+            if node.true_node.is_some() != node.false_node.is_some() && node.next.is_none() {
+                issues.push(TreeIssue::new(
+                    TreeIssueKind::MissingBranch, Some(label), format!(
+                        "node requires both branch targets (missing {})", if node.true_node.is_none() { "true_node" } else { "false_node" }
+                    )
+                ));
+            }
+
+            let is_final = node.final_node.unwrap_or(false);
+
+            if !is_final && !branches && node.next.is_none() {
+                issues.push(TreeIssue::new(
+                    TreeIssueKind::NonFinalLeaf, Some(label), "node has no transitions but is not a final node".to_string()
+                ));
+            }
+
+            match (&node.check, node.node_spec()) {
+                (None, _) => issues.push(TreeIssue::new(
+                    TreeIssueKind::CheckTypeMissing, Some(label), "node requires a check type".to_string()
+                )),
+                (Some(check), None) => issues.push(TreeIssue::new(
+                    TreeIssueKind::SpecMissing, Some(label), format!("node requires a node specification for check type: {check:?}")
+                )),
+                (Some(check), Some(spec)) => {
+                    if !spec.rules.is_empty() {
+                        if parser == Some(NodeParser::Category) {
+                            issues.push(TreeIssue::new(
+                                TreeIssueKind::MissingBranch, Some(label), "rule nodes require true/false branch targets".to_string()
+                            ));
+                        }
+                        continue;
+                    }
+                    let tags = spec.parser.expected_tags();
+                    if tags.is_empty() {
+                        continue;
+                    }
+                    let instructions = match &node.question {
+                        None => {
+                            issues.push(TreeIssue::new(
+                                TreeIssueKind::QuestionMissing, Some(label), format!("node question is required for check type: {check:?}")
+                            ));
+                            continue;
+                        },
+                        Some(Question::Simple(prompt)) => prompt.clone(),
+                        Some(Question::Detailed { tasks, instructions, .. }) => match instructions {
+                            Some(instructions) => format!("{tasks}\n{instructions}"),
+                            None => tasks.clone()
+                        }
+                    };
+                    for tag in tags {
+                        if !instructions.contains(&format!("<{tag}>")) {
+                            issues.push(TreeIssue::new(
+                                TreeIssueKind::InstructionTagMissing, Some(label), format!("tasks and instructions do not ask for <{tag}></{tag}> tags")
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        let graph = DiagnosticAgent::graph(self)?;
+
+        // Reachability from the resolved root or, if it cannot be resolved, from all nodes without incoming transitions
+        let starts: Vec<NodeIndex> = graph.node_indices().filter(|&n| match &root {
+            Some(root) => graph[n].label.as_ref() == Some(root),
+            None => graph.neighbors_directed(n, Direction::Incoming).next().is_none()
+        }).collect();
+
+        let mut reachable = std::collections::HashSet::new();
+        for start in starts {
+            let mut dfs = petgraph::visit::Dfs::new(&graph, start);
+            while let Some(n) = dfs.next(&graph) {
+                reachable.insert(n);
+            }
+        }
+        for n in graph.node_indices() {
+            if !reachable.contains(&n) {
+                issues.push(TreeIssue::new(
+                    TreeIssueKind::UnreachableNode, graph[n].label.as_deref(), "node is not reachable from the root".to_string()
+                ));
+            }
+        }
+
+        // Cycles terminate only if a transition leaves the strongly connected component,
+        // the repeat limit only applies to self-loops from failed answer extraction
+        for component in petgraph::algo::tarjan_scc(&graph) {
+            let cyclic = component.len() > 1 || graph.contains_edge(component[0], component[0]);
+            if !cyclic {
+                continue;
             }
-        )
+            let exits = component.iter().any(|&n| {
+                graph.neighbors_directed(n, Direction::Outgoing).any(|m| !component.contains(&m))
+            });
+            if !exits {
+                let mut labels: Vec<&str> = component.iter().filter_map(|&n| graph[n].label.as_deref()).collect();
+                labels.sort();
+                issues.push(TreeIssue::new(
+                    TreeIssueKind::NonTerminatingCycle, labels.first().copied(), format!("cycle cannot terminate: {}", labels.join(" -> "))
+                ));
+            }
+        }
+
+        Ok(issues)
     }
-    pub fn tiered(task_config: TaskConfig) -> Result<Self, GptError> {
+    pub fn tiered(task_config: TaskConfig, prompts: &PromptLibrary) -> Result<Self, GptError> {
 
         let check_above_threshold = TreeNode::default()
             .label("check_above_threshold")
@@ -905,39 +3492,39 @@ impl DecisionTree {
             .false_node("check_below_threshold")
             .with_check(DiagnosticNode::AboveThresholdQuery)
             .with_tasks(
-                match task_config {
+                prompts.resolve(&match task_config {
                     TaskConfig::Default => NodeTask::DiagnoseDefault,
                     TaskConfig::Simple => NodeTask::DiagnoseSimple,
                     TaskConfig::Tiered => NodeTask::DiagnoseDefaultPrimary
-                }
+                })
             )?
-            .with_instructions(NodeInstruction::DiagnoseDefault)?;
+            .with_instructions(prompts.resolve(&NodeInstruction::DiagnoseDefault))?;
 
         let check_below_threshold = TreeNode::default()
             .label("check_below_threshold")
             .next("check_target_threshold")
             .with_check(DiagnosticNode::BelowThresholdQuery)
             .with_tasks(
-                match task_config {
+                prompts.resolve(&match task_config {
                     TaskConfig::Default => NodeTask::DiagnoseDefault,
                     TaskConfig::Simple => NodeTask::DiagnoseSimple,
                     TaskConfig::Tiered => NodeTask::DiagnoseDefaultSecondary,
-                }
+                })
             )?
-            .with_instructions(NodeInstruction::DiagnoseDefault)?;
+            .with_instructions(prompts.resolve(&NodeInstruction::DiagnoseDefault))?;
         
         let check_target_threshold = TreeNode::default()
             .label("check_target_threshold")
             .next("integrate_thresholds")
             .with_check(DiagnosticNode::TargetThresholdQuery)
             .with_tasks(
-                match task_config {
+                prompts.resolve(&match task_config {
                     TaskConfig::Default => NodeTask::DiagnoseDefault,
                     TaskConfig::Simple => NodeTask::DiagnoseSimple,
                     TaskConfig::Tiered => NodeTask::DiagnoseDefaultTarget,
-                }
+                })
             )?
-            .with_instructions(NodeInstruction::DiagnoseDefault)?;
+            .with_instructions(prompts.resolve(&NodeInstruction::DiagnoseDefault))?;
 
         let integrate_thresholds = TreeNode::default()
             .label("integrate_thresholds")
@@ -945,20 +3532,20 @@ impl DecisionTree {
             .false_node("diagnose_non_infectious")
             .with_check(DiagnosticNode::IntegrateThresholds)
             .with_tasks(
-                match task_config {
+                prompts.resolve(&match task_config {
                     TaskConfig::Default => NodeTask::DiagnoseDefault,
                     TaskConfig::Simple => NodeTask::DiagnoseSimple,
                     TaskConfig::Tiered => NodeTask::DiagnoseDefaultIntegrate,
-                }
+                })
             )?
-            .with_instructions(NodeInstruction::DiagnoseDefault)?;
+            .with_instructions(prompts.resolve(&NodeInstruction::DiagnoseDefault))?;
         
         let diagnose_infectious = TreeNode::default()
             .label("diagnose_infectious")
             .final_node(true)
             .with_check(DiagnosticNode::DiagnoseInfectious)
-            .with_tasks(NodeTask::DiagnoseInfectious)?
-            .with_instructions(NodeInstruction::DiagnoseInfectious)?;
+            .with_tasks(prompts.resolve(&NodeTask::DiagnoseInfectious))?
+            .with_instructions(prompts.resolve(&NodeInstruction::DiagnoseInfectious))?;
         
         let diagnose_non_infectious = TreeNode::default()
             .label("diagnose_non_infectious")
@@ -980,11 +3567,14 @@ impl DecisionTree {
                 version: "0.3.0".to_string(),
                 description: "Tiered decision making process using tiered filter sections of the metagenomic taxonomic profiling data as primary determination of infectious or non-infectious samples".to_string(),
                 max_repeats: 3,
+                root: Some("check_above_threshold".to_string()),
+                review: ReviewPolicy::default(),
+                includes: Vec::new(),
                 nodes: TreeNodes::from_vec(nodes)?
             }
         )
     }
-    pub fn tiered_threshold(task_config: TaskConfig) -> Result<Self, GptError> {
+    pub fn tiered_threshold(task_config: TaskConfig, prompts: &PromptLibrary) -> Result<Self, GptError> {
 
         let check_above_threshold = TreeNode::default()
             .label("check_above_threshold")
@@ -992,13 +3582,13 @@ impl DecisionTree {
             .false_node("check_below_and_target_threshold")
             .with_check(DiagnosticNode::AboveThresholdQuery)
             .with_tasks(
-                match task_config {
+                prompts.resolve(&match task_config {
                     TaskConfig::Default => NodeTask::DiagnoseDefault,
                     TaskConfig::Simple => NodeTask::DiagnoseSimple,
                     TaskConfig::Tiered => NodeTask::DiagnoseDefaultPrimary
-                }
+                })
             )?
-            .with_instructions(NodeInstruction::DiagnoseDefault)?;
+            .with_instructions(prompts.resolve(&NodeInstruction::DiagnoseDefault))?;
 
         let check_below_and_target_threshold = TreeNode::default()
             .label("check_below_and_target_threshold")
@@ -1006,20 +3596,20 @@ impl DecisionTree {
             .false_node("diagnose_non_infectious")
             .with_check(DiagnosticNode::BelowTargetThresholdQuery)
             .with_tasks(
-                match task_config {
+                prompts.resolve(&match task_config {
                     TaskConfig::Default => NodeTask::DiagnoseDefault,
                     TaskConfig::Simple => NodeTask::DiagnoseSimple,
                     TaskConfig::Tiered => NodeTask::DiagnoseDefaultBelowTarget,
-                }
+                })
             )?
-            .with_instructions(NodeInstruction::DiagnoseDefault)?;
+            .with_instructions(prompts.resolve(&NodeInstruction::DiagnoseDefault))?;
         
         let diagnose_infectious = TreeNode::default()
             .label("diagnose_infectious")
             .final_node(true)
             .with_check(DiagnosticNode::DiagnoseInfectious)
-            .with_tasks(NodeTask::DiagnoseInfectious)?
-            .with_instructions(NodeInstruction::DiagnoseInfectious)?;
+            .with_tasks(prompts.resolve(&NodeTask::DiagnoseInfectious))?
+            .with_instructions(prompts.resolve(&NodeInstruction::DiagnoseInfectious))?;
         
         let diagnose_non_infectious = TreeNode::default()
             .label("diagnose_non_infectious")
@@ -1039,12 +3629,49 @@ impl DecisionTree {
                 version: "0.1.0".to_string(),
                 description: "Tiered decision making process using tiered filter sections of the metagenomic taxonomic profiling data as primary determination of infectious or non-infectious samples but limited to a single threshold above and below (below and target filter sections)".to_string(),
                 max_repeats: 3,
+                root: Some("check_above_threshold".to_string()),
+                review: ReviewPolicy::default(),
+                includes: Vec::new(),
                 nodes: TreeNodes::from_vec(nodes)?
             }
         )
     }
 
-    pub fn single_node(task_config: TaskConfig) -> Result<Self, GptError> {
+    pub fn tiered_aneuploidy(task_config: TaskConfig, prompts: &PromptLibrary) -> Result<Self, GptError> {
+
+        let mut tree = Self::tiered(task_config, prompts)?;
+
+        // Samples without an infectious diagnosis are checked for host copy-number evidence of malignancy
+        if let Some(integrate_thresholds) = tree.nodes.get_mut("integrate_thresholds") {
+            integrate_thresholds.false_node = Some("check_aneuploidy".to_string());
+        }
+
+        let check_aneuploidy = TreeNode::default()
+            .label("check_aneuploidy")
+            .true_node("diagnose_tumor")
+            .false_node("diagnose_non_infectious")
+            .with_check(DiagnosticNode::AneuploidyQuery)
+            .with_tasks(prompts.resolve(&NodeTask::DiagnoseTumor))?
+            .with_instructions(prompts.resolve(&NodeInstruction::DiagnoseTumor))?;
+
+        let diagnose_tumor = TreeNode::default()
+            .label("diagnose_tumor")
+            .final_node(true)
+            .with_check(DiagnosticNode::DiagnoseTumor);
+
+        tree.nodes.extend(TreeNodes::from_vec(vec![check_aneuploidy, diagnose_tumor])?);
+        
+        Ok(
+            Self {
+                name: "tiered_aneuploidy".to_string(),
+                version: "0.1.0".to_string(),
+                description: "Tiered decision making process using tiered filter sections of the metagenomic taxonomic profiling data as primary determination of infectious or non-infectious samples, followed by host copy-number evidence of malignancy for non-infectious samples".to_string(),
+                ..tree
+            }
+        )
+    }
+
+    pub fn single_node(task_config: TaskConfig, prompts: &PromptLibrary) -> Result<Self, GptError> {
 
         let check_above_sub_threshold = TreeNode::default()
             .label("check_above_sub_threshold")
@@ -1052,20 +3679,20 @@ impl DecisionTree {
             .false_node("diagnose_non_infectious")
             .with_check(DiagnosticNode::AboveSubThresholdQuery)
             .with_tasks(
-                match task_config {
+                prompts.resolve(&match task_config {
                     TaskConfig::Default => NodeTask::DiagnoseDefault,
                     TaskConfig::Simple => NodeTask::DiagnoseSimple,
                     TaskConfig::Tiered => NodeTask::DiagnoseDefaultPrimary
-                }
+                })
             )?
-            .with_instructions(NodeInstruction::DiagnoseDefault)?;
+            .with_instructions(prompts.resolve(&NodeInstruction::DiagnoseDefault))?;
         
         let diagnose_infectious = TreeNode::default()
             .label("diagnose_infectious")
             .final_node(true)
             .with_check(DiagnosticNode::DiagnoseInfectious)
-            .with_tasks(NodeTask::DiagnoseInfectious)?
-            .with_instructions(NodeInstruction::DiagnoseInfectious)?;
+            .with_tasks(prompts.resolve(&NodeTask::DiagnoseInfectious))?
+            .with_instructions(prompts.resolve(&NodeInstruction::DiagnoseInfectious))?;
         
         let diagnose_non_infectious = TreeNode::default()
             .label("diagnose_non_infectious")
@@ -1084,12 +3711,358 @@ impl DecisionTree {
                 version: "0.1.0".to_string(),
                 description: "Single node decision making process using tiered filter sections of the metagenomic taxonomic profiling data as primary determination of infectious or non-infectious samples".to_string(),
                 max_repeats: 3,
+                root: Some("check_above_sub_threshold".to_string()),
+                review: ReviewPolicy::default(),
+                includes: Vec::new(),
                 nodes: TreeNodes::from_vec(nodes)?
             }
         )
     }
 }
 
+/// Category labels are matched case-insensitive with whitespace as hyphens
+pub fn normalize_category(category: &str) -> String {
+    category.trim().to_lowercase().split_whitespace().collect::<Vec<_>>().join("-")
+}
+
+/// Visit of a node in the execution path of a sample
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeVisit {
+    pub result: Option<bool>,
+    pub category: Option<String>,
+    pub rule: Option<String>,
+    pub visits: usize,
+    pub taxa: usize,
+}
+impl NodeVisit {
+    pub fn annotation(&self) -> String {
+        let decision = match (&self.category, self.result) {
+            (Some(category), _) => category.clone(),
+            (None, Some(true)) => "yes".to_string(),
+            (None, Some(false)) => "no".to_string(),
+            (None, None) => "-".to_string()
+        };
+        let mut annotation = format!("result: {decision} | taxa: {}", self.taxa);
+        if self.visits > 1 {
+            annotation.push_str(&format!(" | repeats: {}", self.visits - 1));
+        }
+        if let Some(rule) = &self.rule {
+            annotation.push_str(&format!("\nrule: {rule}"));
+        }
+        annotation
+    }
+}
+
+/// Execution path of a sample through a decision tree from the agent state memory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathOverlay {
+    pub nodes: BTreeMap<String, NodeVisit>,
+    pub path: Vec<String>,
+    pub final_node: Option<String>,
+    pub diagnosis: Option<Diagnosis>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review_reason: Option<ReviewReason>,
+}
+impl PathOverlay {
+    /// Memories are matched to the tree nodes by label or by check type 
+    /// for agent states without node labels - the final diagnosis is taken 
+    /// from the result if provided, otherwise from the reviewed diagnosis of 
+    /// the state or the last visited node for states of earlier versions
+    pub fn new(tree: &DecisionTree, state: &AgentState, result: Option<&DiagnosticResult>) -> Self {
+        let mut nodes: BTreeMap<String, NodeVisit> = BTreeMap::new();
+        let mut path: Vec<String> = Vec::new();
+
+        for memory in &state.memory {
+            let label = match &memory.label {
+                Some(label) => Some(label.clone()),
+                None => tree.nodes.iter().find(|(_, node)| node.check.as_ref() == Some(&memory.node)).map(|(label, _)| label.clone())
+            };
+            let Some(label) = label else {
+                continue
+            };
+
+            let visit = nodes.entry(label.clone()).or_insert(NodeVisit {
+                result: None,
+                category: None,
+                rule: None,
+                visits: 0,
+                taxa: 0
+            });
+            visit.result = memory.result;
+            visit.category = memory.category.clone();
+            visit.rule = memory.rule.clone();
+            visit.visits += 1;
+            visit.taxa = memory.data.len();
+
+            if path.last() != Some(&label) {
+                path.push(label);
+            }
+        }
+
+        let final_node = path.last().filter(|label| {
+            tree.nodes.get(*label).is_some_and(|node| node.final_node.unwrap_or(false))
+        }).cloned();
+
+        let (diagnosis, review_reason) = match result {
+            Some(result) => (Some(result.diagnosis.clone()), result.review_reason),
+            None => match &state.diagnosis {
+                Some(diagnosis) => (Some(diagnosis.clone()), state.review_reason),
+                None => (
+                    final_node.as_ref()
+                        .and_then(|label| tree.nodes.get(label))
+                        .and_then(|node| node.node_spec())
+                        .and_then(|spec| spec.diagnosis),
+                    None
+                )
+            }
+        };
+
+        Self { nodes, path, final_node, diagnosis, review_reason }
+    }
+    /// Last node of the path - the final node or the node at which the run stopped 
+    /// after the maximum number of repeats or a failed extraction
+    pub fn is_last(&self, label: &str) -> bool {
+        self.path.last().is_some_and(|last| last == label)
+    }
+    /// Diagnosis with the review reason if the diagnosis was reviewed - only 
+    /// returned for the last node of the path
+    pub fn diagnosis_text(&self, label: &str) -> Option<String> {
+        if !self.is_last(label) {
+            return None
+        }
+        let diagnosis = self.diagnosis.as_ref()?;
+        Some(match &self.review_reason {
+            Some(reason) => format!("diagnosis: {diagnosis} (review: {})", serde_plain::to_string(reason).unwrap_or_default()),
+            None => format!("diagnosis: {diagnosis}")
+        })
+    }
+    /// Transition between consecutive nodes of the path
+    pub fn traversed(&self, source: &str, target: &str) -> bool {
+        self.path.windows(2).any(|w| w[0] == source && w[1] == target)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TreeChangeKind {
+    TreeChanged,
+    NodeAdded,
+    NodeRemoved,
+    TransitionChanged,
+    CheckChanged,
+    FinalChanged,
+    SpecChanged,
+    GenerationChanged,
+    ExamplesChanged,
+    QuestionChanged
+}
+
+/// Change of a decision tree field or node between two tree versions
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TreeChange {
+    pub kind: TreeChangeKind,
+    pub node: Option<String>,
+    pub field: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    /// Unified line diff of changed text fields
+    pub diff: Option<String>,
+}
+impl TreeChange {
+    fn new(kind: TreeChangeKind, node: Option<&str>, field: &str, before: Option<String>, after: Option<String>) -> Self {
+        Self { kind, node: node.map(String::from), field: field.to_string(), before, after, diff: None }
+    }
+    fn text(kind: TreeChangeKind, node: &str, field: &str, before: &str, after: &str) -> Self {
+        let diff = Self::unified_diff(&format!("{node}.{field}"), before, after);
+        Self { diff: Some(diff), ..Self::new(kind, Some(node), field, None, None) }
+    }
+    /// Text change of a tree-level field
+    fn tree_text(kind: TreeChangeKind, field: &str, before: &str, after: &str) -> Self {
+        let diff = Self::unified_diff(field, before, after);
+        Self { diff: Some(diff), ..Self::new(kind, None, field, None, None) }
+    }
+    fn unified_diff(header: &str, before: &str, after: &str) -> String {
+        similar::TextDiff::from_lines(before, after)
+            .unified_diff()
+            .context_radius(2)
+            .missing_newline_hint(false)
+            .header(&format!("{header} (before)"), &format!("{header} (after)"))
+            .to_string()
+    }
+}
+
+/// Frequency of an execution path with a final diagnosis in a cohort
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathFrequency {
+    pub path: String,
+    pub diagnosis: String,
+    pub review_reason: Option<String>,
+    pub samples: usize,
+    pub fraction: f64,
+}
+
+/// Execution paths of a cohort of samples through a decision tree
+#[derive(Debug, Clone)]
+pub struct CohortPaths {
+    pub samples: Vec<(String, PathOverlay)>
+}
+impl CohortPaths {
+    /// Read the agent states (.json) of a directory, sample identifiers are the file names
+    pub fn from_dir(tree: &DecisionTree, dir: &Path) -> Result<Self, GptError> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        files.sort();
+
+        let mut samples = Vec::new();
+        for file in files {
+            let state = match AgentState::from_json(&file) {
+                Ok(state) => state,
+                Err(err) => {
+                    log::warn!("Skipping file that is not an agent state: {} ({err})", file.display());
+                    continue
+                }
+            };
+            let sample = file.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+            samples.push((sample, PathOverlay::new(tree, &state, None)));
+        }
+        Ok(Self { samples })
+    }
+    /// Path frequencies by final diagnosis, ordered by descending frequency
+    pub fn path_frequencies(&self) -> Vec<PathFrequency> {
+        let mut counts: BTreeMap<(String, String, Option<String>), usize> = BTreeMap::new();
+        for (_, overlay) in &self.samples {
+            let diagnosis = overlay.diagnosis.as_ref().unwrap_or(&Diagnosis::Unknown).to_string();
+            let review_reason = overlay.review_reason.map(|reason| serde_plain::to_string(&reason).unwrap_or_default());
+            *counts.entry((overlay.path.join(" -> "), diagnosis, review_reason)).or_insert(0) += 1;
+        }
+        let total = self.samples.len().max(1);
+
+        let mut frequencies: Vec<PathFrequency> = counts.into_iter().map(|((path, diagnosis, review_reason), samples)| PathFrequency {
+            path,
+            diagnosis,
+            review_reason,
+            samples,
+            fraction: samples as f64 / total as f64
+        }).collect();
+        frequencies.sort_by_key(|frequency| std::cmp::Reverse(frequency.samples));
+        frequencies
+    }
+    /// Sub-graphs of the decision tree graph with the traversed nodes and transitions of each sample
+    pub fn graphs(&self, tree: &DecisionTree) -> Result<Vec<Graph<TreeNode, TreeEdge>>, GptError> {
+        let graph = DiagnosticAgent::graph(tree)?;
+        let label = |n: NodeIndex| graph[n].label.clone().unwrap_or_default();
+
+        Ok(self.samples.iter().map(|(_, overlay)| {
+            graph.filter_map(
+                |n, node| overlay.nodes.contains_key(&label(n)).then(|| node.clone()),
+                |e, edge| {
+                    let (source, target) = graph.edge_endpoints(e)?;
+                    overlay.traversed(&label(source), &label(target)).then(|| edge.clone())
+                }
+            )
+        }).collect())
+    }
+}
+
+/// Output formats of decision tree files and graphs
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, clap::ValueEnum)]
+pub enum TreeFormat {
+    Json,
+    Yaml,
+    Dot,
+    Mermaid
+}
+impl TreeFormat {
+    /// Format from the file extension (.yaml, .yml, .dot, .gv, .mmd) or JSON
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => TreeFormat::Yaml,
+            Some("dot") | Some("gv") => TreeFormat::Dot,
+            Some("mmd") | Some("mermaid") => TreeFormat::Mermaid,
+            _ => TreeFormat::Json
+        }
+    }
+}
+
+/// Graph node shapes by node type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeShape {
+    Decision,
+    Category,
+    Rule,
+    Final,
+    Process
+}
+impl NodeShape {
+    pub fn from_node(node: &TreeNode) -> Self {
+        if node.final_node.unwrap_or(false) {
+            return NodeShape::Final
+        }
+        match node.node_spec() {
+            Some(spec) if !spec.rules.is_empty() => NodeShape::Rule,
+            Some(spec) if spec.parser == NodeParser::Category => NodeShape::Category,
+            Some(spec) if spec.parser == NodeParser::Result => NodeShape::Decision,
+            _ => NodeShape::Process
+        }
+    }
+    pub fn dot(&self) -> &'static str {
+        match self {
+            NodeShape::Decision => "diamond",
+            NodeShape::Category => "hexagon",
+            NodeShape::Rule => "parallelogram",
+            NodeShape::Final => "doubleoctagon",
+            NodeShape::Process => "box"
+        }
+    }
+    pub fn mermaid(&self) -> (&'static str, &'static str) {
+        match self {
+            NodeShape::Decision => ("{", "}"),
+            NodeShape::Category => ("{{", "}}"),
+            NodeShape::Rule => ("[/", "/]"),
+            NodeShape::Final => ("([", "])"),
+            NodeShape::Process => ("[", "]")
+        }
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn mermaid_escape(s: &str) -> String {
+    s.replace('"', "#quot;").replace('|', "#124;")
+}
+
+/// Mermaid node identifiers of the labels - sanitized labels that collide with 
+/// another label (e.g. `a.b` and `a_b`) or a reserved word get a numeric suffix
+fn mermaid_ids<'a>(labels: impl Iterator<Item = &'a str>) -> HashMap<String, String> {
+    const RESERVED: [&str; 9] = ["end", "graph", "flowchart", "subgraph", "style", "class", "classdef", "click", "linkstyle"];
+
+    let mut ids = HashMap::new();
+    let mut used = HashSet::new();
+    for label in labels {
+        if ids.contains_key(label) {
+            continue;
+        }
+        let base: String = label.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+        let mut id = base.clone();
+        let mut n = 1;
+        while RESERVED.contains(&id.to_lowercase().as_str()) || used.contains(&id) {
+            id = format!("{base}_{n}");
+            n += 1;
+        }
+        used.insert(id.clone());
+        ids.insert(label.to_string(), id);
+    }
+    ids
+}
+
+fn is_yaml(path: &Path) -> bool {
+    matches!(path.extension().and_then(|e| e.to_str()), Some("yaml") | Some("yml"))
+}
+
 fn dedent(input: &str) -> String {
 
     let lines: Vec<&str> = input
@@ -1121,6 +4094,7 @@ fn dedent(input: &str) -> String {
 pub enum TreeConfig {
     Tiered,
     TieredThreshold,
+    TieredAneuploidy,
     SingleNode
 }
 
@@ -1128,23 +4102,52 @@ pub struct DiagnosticAgent {
     pub state: AgentState,
     pub tree: DecisionTree,
     pub graph: Graph<TreeNode, TreeEdge>,
+    pub prompts: PromptLibrary,
+    pub examples: ExampleLibrary,
+    pub renderer: TaxaRenderer,
+    pub contaminants: ContaminantRegistry,
 }
 
 impl DiagnosticAgent {
     pub fn new(task_config: TaskConfig, tree_config: TreeConfig) -> Result<Self, GptError> {
-        
-        let tree = match tree_config {
-            TreeConfig::Tiered => DecisionTree::tiered(task_config)?,
-            TreeConfig::TieredThreshold => DecisionTree::tiered_threshold(task_config)?,
-            TreeConfig::SingleNode => DecisionTree::single_node(task_config)?
-        };
-        
+        Self::from_tree(
+            DecisionTree::from_config(tree_config, task_config)?
+        )
+    }
+    pub fn from_tree(tree: DecisionTree) -> Result<Self, GptError> {
         Ok(DiagnosticAgent {
             tree: tree.clone(),
             state: AgentState::new(),
-            graph: Self::graph(&tree)?
+            graph: Self::graph(&tree)?,
+            prompts: PromptLibrary::default(),
+            examples: ExampleLibrary::default(),
+            renderer: TaxaRenderer::default(),
+            contaminants: ContaminantRegistry::default()
         })
     }
+    /// Prompt library for the agent primer and assay context - prompts of the 
+    /// built-in trees are resolved when the tree is built (`DecisionTree::from_prompts`)
+    pub fn with_prompts(mut self, prompts: PromptLibrary) -> Self {
+        self.prompts = prompts;
+        self
+    }
+    /// Few-shot example library for nodes that reference examples - all 
+    /// referenced examples must be in the library
+    pub fn with_examples(mut self, examples: ExampleLibrary) -> Result<Self, GptError> {
+        examples.check(&self.tree)?;
+        self.examples = examples;
+        Ok(self)
+    }
+    /// Default rendering of the candidate taxa for nodes without a renderer
+    pub fn with_renderer(mut self, renderer: TaxaRenderer) -> Self {
+        self.renderer = renderer;
+        self
+    }
+    /// Laboratory contaminants annotated in the candidate taxa
+    pub fn with_contaminants(mut self, contaminants: ContaminantRegistry) -> Self {
+        self.contaminants = contaminants;
+        self
+    }
     // Collapses GTDB species variants and sums the taxon evidence for
     // each combination of (id, tool, mode) returned from the taxon
     // retrieval request with the standard settings - the filter config
@@ -1262,8 +4265,9 @@ impl DiagnosticAgent {
     pub fn run_local(
         &mut self, 
         prefetch: PrefetchData,
-        text_generator: &mut TextGenerator, 
-        sample_context: Option<SampleContext>, 
+        aneuploidy: Option<AneuploidyEvidence>,
+        models: &mut ModelPool, 
+        sample_type: Option<SampleType>, 
         clinical_context: Option<ClinicalContext>, 
         assay_context: Option<AssayContext>, 
         agent_primer: Option<AgentPrimer>,
@@ -1273,661 +4277,266 @@ impl DiagnosticAgent {
 
         self.state.post_filter_config = post_filter.clone();
 
-        let mut node_label = match self.tree.name.as_str() {
-            "single_node" => "check_above_sub_threshold",
-            _ => "check_above_threshold",
-        }.to_string();
+        let mut node_label = self.tree.root_label()?;
         
 
-        let assay_ctx = match assay_context {
-            Some(context) => context.text(),
-            None => AssayContext::None.text()
-        };
+        let assay_id = assay_context.as_ref().map(value_id);
+        let assay_ctx = self.prompts.resolve(&assay_context.unwrap_or(AssayContext::None));
+        let primer = agent_primer.map(|primer| self.prompts.resolve(&primer));
 
-        let sample_ctx = match sample_context {
-            Some(context) => context.text(),
+        let sample_ctx = match &sample_type {
+            Some(sample_type) => sample_type.text(),
             None => SampleContext::None.text()
-        };
-        
-        let clinical_ctx = match clinical_context {
-            Some(context) => context.text(),
-            None => ClinicalContext::None.text()
-        };
-
-        let context = format!(
-            "{}\n{}\n{}\n", 
-            dedent(&assay_ctx), 
-            dedent(&sample_ctx),
-            dedent(&clinical_ctx)
-        );
-
-        let mut result = DiagnosticResult {
-            diagnosis: Diagnosis::Unknown,
-            candidates: Vec::new(),
-            pathogen: None
-        };
-
-        let log_id = format!("[{}]", prefetch.config.sample);
-
-        while let Some(node_ref) = self.tree.nodes.get(&node_label) {
-
-            let current_node = node_ref.clone();
-
-            log::info!("{log_id} Processing node: {}", node_label);
-            
-            match current_node.check {
-                Some(DiagnosticNode::AboveThresholdQuery) => {
-
-                    log::info!("{log_id} AboveThreshold");
-                    
-                    let primary_taxa = prefetch.primary.clone();
-
-                    log::info!("{log_id} Primary taxa: {}", primary_taxa.len());
-
-                    let primary_taxa = if let Some(ref post_filter) = post_filter {
-                        Self::apply_post_filter(primary_taxa, post_filter)?
-                    } else {
-                        primary_taxa
-                    };
-
-
-                    log::info!("{log_id} Primary taxa post filter: {}", primary_taxa.len());
-                    
-
-                    let (result, _, prompt, thoughts, answer) = if !primary_taxa.is_empty() {
-
-                        let candidates = ThresholdCandidates::from_primary_threshold(
-                            primary_taxa.clone()
-                        ).to_str(true);
-
-                        let prompt = current_node
-                            .clone()
-                            .with_context(&context)?
-                            .with_data(&candidates)?
-                            .question
-                            .unwrap()
-                            .to_standard_prompt(&agent_primer);
-                        
-                        log::debug!("\n\n{prompt}");
-
-                        let (thoughts, answer) = text_generator.run(&prompt, disable_thinking)?;
-                        
-                        log::debug!("{thoughts}\n\n");
-                        log::debug!("{answer}");
-
-                        (Self::extract_result(&answer, disable_thinking)?, None::<String>, Some(prompt), Some(thoughts), Some(answer))
-                    } else {
-                        log::info!("{log_id} No data retrieved for this node");
-                        (Some(false), None, None, None, None) // no taxa detected
-                    };
-
-                    self.state.memorize(
-                        DiagnosticMemory::new(
-                            DiagnosticNode::AboveThresholdQuery, 
-                            primary_taxa, 
-                            result, 
-                            prompt, 
-                            thoughts, 
-                            answer
-                        )
-                    );
-                    
-                    match self.get_next_node_label(&current_node, result, &log_id)? {
-                        Some(label) => node_label = label,
-                        None => break
-                    }
-                },
-                Some(DiagnosticNode::AboveSubThresholdQuery) => {
-
-                    log::info!("{log_id} AboveSubThreshold");
-                    
-                    let mut combined_taxa = Vec::new();
-
-                    let primary_taxa = prefetch.primary.clone();
-                    log::info!("{log_id} Primary taxa: {}", primary_taxa.len());
-                    let primary_taxa = if let Some(ref post_filter) = post_filter {
-                        Self::apply_post_filter(primary_taxa, post_filter)?
-                    } else {
-                        primary_taxa
-                    };
-                    log::info!("{log_id} Primary taxa post filter: {}", primary_taxa.len());
-                    combined_taxa.extend_from_slice(&primary_taxa);
-
-                    let secondary_taxa = prefetch.secondary.clone();
-                    log::info!("{log_id} Secondary taxa: {}", secondary_taxa.len());
-                    let secondary_taxa = if let Some(ref post_filter) = post_filter {
-                        Self::apply_post_filter(secondary_taxa, post_filter)?
-                    } else {
-                        secondary_taxa
-                    };
-                    log::info!("{log_id} Secondary taxa post filter: {}", secondary_taxa.len());
-                    combined_taxa.extend_from_slice(&secondary_taxa);
-
-
-                    let target_taxa = prefetch.target.clone();
-                    log::info!("{log_id} Target taxa: {}", target_taxa.len());
-                    let target_taxa = if let Some(ref post_filter) = post_filter {
-                        Self::apply_post_filter(target_taxa, post_filter)?
-                    } else {
-                        target_taxa
-                    };
-                    log::info!("{log_id} Target taxa post filter: {}", target_taxa.len());
-                    combined_taxa.extend_from_slice(&target_taxa);
-                    
-
-                    let (result, _, prompt, thoughts, answer) = if !primary_taxa.is_empty() {
-
-                        let primary_candidates = ThresholdCandidates::from_primary_threshold(
-                            primary_taxa.clone()
-                        ).to_str(true);
-
-                        let secondary_candidates = ThresholdCandidates::from_secondary_threshold(
-                            secondary_taxa.clone()
-                        ).to_str(true);
-
-                        let target_candidates = ThresholdCandidates::from_target_threshold(
-                            target_taxa.clone()
-                        ).to_str(true);
-
-                        let candidates = format!("{primary_candidates}\n\n{secondary_candidates}\n\n{target_candidates}");
-
-                        let prompt = current_node
-                            .clone()
-                            .with_context(&context)?
-                            .with_data(&candidates)?
-                            .question
-                            .unwrap()
-                            .to_standard_prompt(&agent_primer);
-                        
-                        log::debug!("\n\n{prompt}");
-
-                        let (thoughts, answer) = text_generator.run(&prompt, disable_thinking)?;
-                        
-                        log::debug!("{thoughts}\n\n");
-                        log::debug!("{answer}");
-
-                        (Self::extract_result(&answer, disable_thinking)?, None::<String>, Some(prompt), Some(thoughts), Some(answer))
-                    } else {
-                        log::info!("{log_id} No data retrieved for this node");
-                        (Some(false), None, None, None, None) // no taxa detected
-                    };
-
-                    self.state.memorize(
-                        DiagnosticMemory::new(
-                            DiagnosticNode::AboveSubThresholdQuery, 
-                            combined_taxa, 
-                            result, 
-                            prompt, 
-                            thoughts, 
-                            answer
-                        )
-                    );
-                    
-                    match self.get_next_node_label(&current_node, result, &log_id)? {
-                        Some(label) => node_label = label,
-                        None => break
-                    }
-                },
-
-                Some(DiagnosticNode::BelowThresholdQuery) => {
-                    
-                    log::info!("{log_id} BelowThreshold");
-
-                    let secondary_taxa = prefetch.secondary.clone();
-
-                    log::info!("{log_id} Secondary taxa: {}", secondary_taxa.len());
-
-                    let secondary_taxa = if let Some(ref post_filter) = post_filter {
-                        Self::apply_post_filter(secondary_taxa, post_filter)?
-                    } else {
-                        secondary_taxa
-                    };
-
-                    log::info!("{log_id} Secondary taxa post filter: {}", secondary_taxa.len());
-
-                    let (result, _, prompt, thoughts, answer) = if !secondary_taxa.is_empty() {
-
-                        let candidates = ThresholdCandidates::from_secondary_threshold(
-                            secondary_taxa.clone()
-                        ).to_str(true);
-
-                        let prompt = current_node
-                            .clone()
-                            .with_context(&context)?
-                            .with_data(&candidates)?
-                            .question
-                            .unwrap()
-                            .to_standard_prompt(&agent_primer);
-                        
-                        log::debug!("\n\n{prompt}");
-
-                        let (thoughts, answer) = text_generator.run(&prompt, disable_thinking)?;
-                        
-                        log::debug!("{thoughts}\n\n");
-                        log::debug!("{answer}");
-
-                        (Self::extract_result(&answer,disable_thinking)?, None::<String>, Some(prompt), Some(thoughts), Some(answer))
-                    } else {
-                        log::info!("{log_id} No data retrieved for this node");
-                        (Some(false), None, None, None, None) // no taxa detected
-                    };
-
-                    self.state.memorize(
-                        DiagnosticMemory::new(
-                            DiagnosticNode::BelowThresholdQuery, 
-                            secondary_taxa, 
-                            result, 
-                            prompt, 
-                            thoughts, 
-                            answer
-                        )
-
-                    );
-                    
-                    match self.get_next_node_label(&current_node, result, &log_id)? {
-                        Some(label) => node_label = label,
-                        None => break
-                    }
-                    
-                },
-                Some(DiagnosticNode::BelowTargetThresholdQuery) => {
-
-                log::info!("{log_id} SubThreshold = BelowThreshold + TargetThreshold");
-
-
-                    let secondary_taxa = prefetch.secondary.clone();
-                    log::info!("{log_id} Secondary taxa: {}", secondary_taxa.len());
-                    let secondary_taxa = if let Some(ref post_filter) = post_filter {
-                        Self::apply_post_filter(secondary_taxa, post_filter)?
-                    } else {
-                        secondary_taxa
-                    };
-                    log::info!("{log_id} Secondary taxa post filter: {}", secondary_taxa.len());
-
-
-                    let target_taxa = prefetch.target.clone();
-                    log::info!("{log_id} Target taxa: {}", target_taxa.len());
-                    let target_taxa = if let Some(ref post_filter) = post_filter {
-                        Self::apply_post_filter(target_taxa, post_filter)?
-                    } else {
-                        target_taxa
-                    };
-                    log::info!("{log_id} Target taxa post filter: {}", target_taxa.len());
-
-                    let (result, _, prompt, thoughts, answer) = if target_taxa.is_empty() && secondary_taxa.is_empty() {
-                        log::info!("{log_id} No data retrieved for this node");
-                        (Some(false), None, None, None, None) // no taxa detected
-                    } else {
+        };
+        
+        let clinical_ctx = match &clinical_context {
+            Some(context) => context.text(),
+            None => ClinicalContext::None.text()
+        };
 
-                        let secondary_candidates = ThresholdCandidates::from_secondary_threshold(
-                            secondary_taxa.clone()
-                        ).to_str(true);
+        let context = format!(
+            "{}\n{}\n{}\n", 
+            dedent(&assay_ctx), 
+            dedent(&sample_ctx),
+            dedent(&clinical_ctx)
+        );
 
-                        let target_candidates = ThresholdCandidates::from_target_threshold(
-                            target_taxa.clone()
-                        ).to_str(true);
+        let mut result = DiagnosticResult {
+            diagnosis: Diagnosis::Unknown,
+            candidates: Vec::new(),
+            pathogen: None,
+            review_reason: None
+        };
 
-                        let candidates = format!("{secondary_candidates}\n\n{target_candidates}");
+        let mut signals = Vec::new();
 
-                        let prompt = current_node
-                            .clone()
-                            .with_context(&context)?
-                            .with_data(&candidates)?
-                            .question
-                            .unwrap()
-                            .to_standard_prompt(&agent_primer);
-                        
-                        log::debug!("\n\n{prompt}");
+        let log_id = format!("[{}]", prefetch.config.sample);
 
-                        let (thoughts, answer) = text_generator.run(&prompt, disable_thinking)?;
-                        
-                        log::debug!("{thoughts}\n\n");
-                        log::debug!("{answer}");
+        while let Some(node_ref) = self.tree.nodes.get(&node_label) {
 
-                        (Self::extract_result(&answer, disable_thinking)?, None::<String>, Some(prompt), Some(thoughts), Some(answer))
-                    };
-                    
-                    self.state.memorize(
-                        DiagnosticMemory::new(
-                            DiagnosticNode::BelowTargetThresholdQuery, 
-                            [secondary_taxa, target_taxa].concat(), 
-                            result, 
-                            prompt, 
-                            thoughts, 
-                            answer
-                        )
-                    );
+            let current_node = node_ref.clone();
 
-                    match self.get_next_node_label(&current_node, result, &log_id)? {
-                        Some(label) => node_label = label,
-                        None => break
-                    }
-                    
-                },
-                Some(DiagnosticNode::TargetThresholdQuery) => {
+            log::info!("{log_id} Processing node: {}", node_label);
 
-                    log::info!("{log_id} TargetThreshold");
+            let spec = match current_node.node_spec() {
+                Some(spec) => spec,
+                None => {
+                    log::warn!("Node processing not implemented for node: {}", current_node.label.unwrap_or("no_label".to_string()));
+                    break
+                }
+            };
+
+            // Prefetch tiers are post-filtered, memories were filtered when they were memorized
+            let mut tiers = Vec::new();
+            for tier in &spec.tiers {
+                let taxa = tier.taxa(&prefetch);
+                log::info!("{log_id} {} taxa: {}", tier.name(), taxa.len());
+                let taxa = if let Some(ref post_filter) = post_filter {
+                    Self::apply_post_filter(taxa, post_filter)?
+                } else {
+                    taxa
+                };
+                log::info!("{log_id} {} taxa post filter: {}", tier.name(), taxa.len());
+                tiers.push((tier.clone(), taxa));
+            }
 
-                    let target_taxa = prefetch.target.clone();
-                    log::info!("{log_id} Target taxa: {}", target_taxa.len());
+            let inputs = self.state.node_inputs(&spec, &tiers);
 
-                    let target_taxa = if let Some(ref post_filter) = post_filter {
-                        Self::apply_post_filter(target_taxa, post_filter)?
-                    } else {
-                        target_taxa
-                    };
-                    log::info!("{log_id} Target taxa post filter: {}", target_taxa.len());
-                    
-                    let (result, _, prompt, thoughts, answer) = if !target_taxa.is_empty() {
-
-                        let candidates = ThresholdCandidates::from_target_threshold(
-                            target_taxa.clone()
-                        ).to_str(true);
-
-                        let prompt = current_node
-                            .clone()
-                            .with_context(&context)?
-                            .with_data(&candidates)?
-                            .question
-                            .unwrap()
-                            .to_standard_prompt(&agent_primer);
-                        
-                        log::debug!("\n\n{prompt}");
-
-                        let (thoughts, answer) = text_generator.run(&prompt, disable_thinking)?;
-                        
-                        log::debug!("{thoughts}\n\n");
-                        log::debug!("{answer}");
-
-                        (Self::extract_result(&answer, disable_thinking)?, None::<String>, Some(prompt), Some(thoughts), Some(answer))
-                    } else {
-                        log::info!("{log_id} No data retrieved for this node");
-                        (Some(false), None, None, None, None) // no taxa detected
-                    };
-                    
-                    self.state.memorize(
-                        DiagnosticMemory::new(
-                            DiagnosticNode::TargetThresholdQuery, 
-                            target_taxa, 
-                            result, 
-                            prompt, 
-                            thoughts, 
-                            answer
-                        )
-                    );
+            if conflicting_inputs(&inputs) {
+                log::info!("{log_id} Conflicting decisions in memory inputs");
+                signals.push(ReviewReason::ConflictingTiers);
+            }
 
-                    match self.get_next_node_label(&current_node, result, &log_id)? {
-                        Some(label) => node_label = label,
-                        None => break
-                    }
-                    
-                },
-                Some(DiagnosticNode::IntegrateThresholds) => {
-                    
-                    // Retrieve the below and target threshold data and result memories
-                    log::info!("{log_id} IntegrateThresholds");
-                    let secondary_memory = self.state.retrieve(DiagnosticNode::BelowThresholdQuery).cloned();
-                    let target_memory = self.state.retrieve(DiagnosticNode::TargetThresholdQuery).cloned();
-
-                    match (
-                        secondary_memory, 
-                        target_memory
-                    ) {
-                        (
-                            Some(secondary_memory), 
-                            Some(target_memory)
-                        ) => {
-                            match (
-                                secondary_memory.data.is_empty(), 
-                                target_memory.data.is_empty()
-                            ) {
-                                // Continue with diagnosis if data was available in one of the diagnostic nodes but not the other
-                                // use the result from that stage to continue in the decision tree
-                                (false, true) => {
-                                    log::info!("{log_id} Data only from secondary threshold node - continue to next node with result from secondary threshold node");
-
-                                    let mut secondary_memory_integrated = secondary_memory.clone();
-                                    secondary_memory_integrated.node = DiagnosticNode::IntegrateThresholds;
-
-                                    self.state.memorize(secondary_memory_integrated);
-
-                                    match self.get_next_node_label(&current_node, secondary_memory.result, &log_id)? {
-                                        Some(label) => node_label = label,
-                                        None => break
-                                    }
-                                },
-                                (true, false) => {
-                                    let mut target_memory_integrated = target_memory.clone();
-                                    target_memory_integrated.node = DiagnosticNode::IntegrateThresholds;
+            let data: Vec<Taxon> = inputs.iter().flat_map(|(_, taxa, _)| taxa.clone()).collect();
 
-                                    self.state.memorize(target_memory_integrated);
+            let host_evidence = match &aneuploidy {
+                Some(evidence) if spec.aneuploidy && !evidence.is_empty() => Some(evidence.to_str()),
+                _ => None
+            };
 
-                                    log::info!("{log_id} Data only from target threshold node - continue to next node with result from target threshold node");
-                                    match self.get_next_node_label(&current_node, target_memory.result, &log_id)? {
-                                        Some(label) => node_label = label,
-                                        None => break
-                                    }
-                                },
-                                (true, true) => {
-                                    // No data from the sub-threshold nodes mean we didn't make a diagnosis in the primary threshold either 
-                                    // so we assign the final result as non-infectious
-                                    self.state.memorize(
-                                        DiagnosticMemory::non_infectious(
-                                            DiagnosticNode::IntegrateThresholds
-                                        )
-                                    );
-
-                                    node_label = String::from("diagnose_non_infectious")
-                                },
-                                // Continue with integration node processing and decision making if bnoth nodes had data available -
-                                // this will combine the data for the integration node prompt and override the decisions made previously
-                                (false, false) => {
-
-                                    let below_threshold_data = ThresholdCandidates::from_secondary_threshold(
-                                        secondary_memory.data.clone()
-                                    ).to_str(true);
-
-                                    let target_threshold_data = ThresholdCandidates::from_target_threshold(
-                                        target_memory.data.clone()
-                                    ).to_str(true);
-                                    
-                                    let candidates = format!("{}\n\n{}", below_threshold_data, target_threshold_data);
-
-                                    let prompt = current_node
-                                        .clone()
-                                        .with_context(&context)?
-                                        .with_data(&candidates)?
-                                        .question
-                                        .unwrap()
-                                        .to_standard_prompt(&agent_primer);
-                                    
-                                    log::debug!("\n\n{prompt}");
-            
-                                    let (thoughts, answer) = text_generator.run(&prompt, disable_thinking)?;
-                                    
-                                    log::debug!("{thoughts}\n\n");
-                                    log::debug!("{answer}");
-
-                                    let result = Self::extract_result(&answer,disable_thinking)?;
-
-                                    let data = [
-                                        secondary_memory.data.clone(), 
-                                        target_memory.data.clone()
-                                    ].concat();
-
-                                    self.state.memorize(
-                                        DiagnosticMemory::new(
-                                            DiagnosticNode::IntegrateThresholds, 
-                                            data, 
-                                            result, 
-                                            Some(prompt), 
-                                            Some(thoughts), 
-                                            Some(answer)
-                                        )
-                                    );
-
-                                    match self.get_next_node_label(&current_node, result, &log_id)? {
-                                        Some(label) => node_label = label,
-                                        None => break
-                                    }
-
-                                }
-                            }
-                        },
-                        // If no memories were available for either of the low abundance diagnostic nodes
-                        _ => {
-                            log::warn!("No data available for the integration node - this should not happen!");
-                            break
-                        }
-                    }
+            let required_empty = spec.required.iter().any(|required| {
+                tiers.iter().any(|(tier, taxa)| tier == required && taxa.is_empty())
+            });
+            let has_data = inputs.iter().any(|(_, taxa, _)| !taxa.is_empty()) || host_evidence.is_some();
 
-                },
-                Some(DiagnosticNode::DiagnoseInfectious) => {
-                    
-                    let mut candidates = String::new();
-                    let mut memory_candidates = Vec::new();
-
-                    if let Some(memory) = self.state.retrieve(DiagnosticNode::AboveThresholdQuery) {
-                        if let Some(result) = memory.result {
-                            if result {
-                                let primary_candidates = ThresholdCandidates::from_primary_threshold(
-                                    memory.data.clone()
-                                ).to_str(true);
-                                
-                                if !primary_candidates.is_empty() {
-                                    candidates.push_str(&primary_candidates);
-                                    candidates.push_str("\n\n");
-                                }
-                                
-                                memory_candidates.extend_from_slice(&memory.data);
-                            }
-                        };
-                    };
+            let memory = if !spec.rules.is_empty() {
 
+                let phage_list = post_filter.as_ref().map(|config| config.exclude_phage_list.clone()).unwrap_or_default();
+                let fired = spec.rules.iter().find(|rule| rule.evaluate(&data, &phage_list));
 
-                    if let Some(memory) = self.state.retrieve(DiagnosticNode::AboveSubThresholdQuery) {
-                        if let Some(result) = memory.result {
-                            if result {
-                                let combined_candidates = ThresholdCandidates::from_combined_threshold(
-                                    memory.data.clone()
-                                ).to_str(true);
-                                
-                                if !combined_candidates.is_empty() {
-                                    candidates.push_str(&combined_candidates);
-                                    candidates.push_str("\n\n");
-                                }
+                match fired {
+                    Some(rule) => log::info!("{log_id} Rule fired: {}", rule.name),
+                    None => log::info!("{log_id} No rule fired")
+                }
 
-                                memory_candidates.extend_from_slice(&memory.data);
-                            }
-                        };
-                    };
+                DiagnosticMemory::new(
+                    DiagnosticNode::Generic, 
+                    data, 
+                    Some(fired.is_some()), 
+                    None, 
+                    None, 
+                    None
+                ).with_rule(fired.map(|rule| rule.name.clone()))
 
+            } else if spec.prompt_empty || (!required_empty && has_data) {
 
-                    if let Some(memory) = self.state.retrieve(DiagnosticNode::IntegrateThresholds) {
-                        if let Some(result) = memory.result {
-                            if result {
-                                let integrate_candidates = ThresholdCandidates::from_integrate_threshold(
-                                    memory.data.clone()
-                                ).to_str(true);
-                                
-                                if !integrate_candidates.is_empty() {
-                                    candidates.push_str(&integrate_candidates);
-                                    candidates.push_str("\n\n");
-                                }
-                                memory_candidates.extend_from_slice(&memory.data);
-                            }
+                if let Some(memory) = single_memory_input(&inputs).filter(|_| spec.reuse_single) {
+                    log::info!("{log_id} Data only from a single input node - continue with the result from that node");
+                    memory.clone()
+                } else {
+                    let renderer = spec.render.as_ref().unwrap_or(&self.renderer);
+                    let annotations = self.contaminants.annotations(
+                        &data, 
+                        assay_id.as_deref(), 
+                        sample_type.as_ref().map(|t| t.id.as_str())
+                    );
+                    let candidates = inputs
+                        .iter()
+                        .map(|(label, taxa, _)| label.render_with(taxa.clone(), renderer, &annotations))
+                        .collect::<Result<Vec<_>, _>>()?
+                        .into_iter()
+                        .chain(host_evidence.clone())
+                        .collect::<Vec<_>>()
+                        .join("\n\n");
+
+                    let variables = PromptVariables {
+                        node: node_label.clone(),
+                        sample: prefetch.config.sample.clone(),
+                        sample_type: sample_type.as_ref().map(|sample_type| sample_type.name.clone()),
+                        specimen: sample_type.clone(),
+                        clinical_notes: match &clinical_context {
+                            Some(ClinicalContext::Custom(notes)) => Some(notes.clone()),
+                            Some(ClinicalContext::Record(record)) => record.notes.clone(),
+                            _ => None
+                        },
+                        clinical: match &clinical_context {
+                            Some(ClinicalContext::Record(record)) => Some(record.clone()),
+                            _ => None
+                        },
+                        assay: dedent(&assay_ctx),
+                        context: context.clone(),
+                        data: candidates,
+                        taxa: data.clone(),
+                        counts: inputs.iter().map(|(label, taxa, _)| (String::from(label.clone()), taxa.len())).collect(),
+                        host_evidence: host_evidence.clone(),
+                        answers: self.state.answers(),
+                        examples: match current_node.examples() {
+                            Some(examples) => self.examples.render(examples, sample_type.as_ref().map(|t| t.id.as_str()))?,
+                            None => None
                         }
                     };
 
+                    self.state.prompts.insert(format!("node.{node_label}"), current_node.question_digest());
 
-                    if let Some(memory) = self.state.retrieve(DiagnosticNode::BelowTargetThresholdQuery) {
-                        if let Some(result) = memory.result {
-                            if result {
-                                let below_target_candidates = ThresholdCandidates::from_integrate_threshold(
-                                    memory.data.clone()
-                                ).to_str(true);
-                                
-                                if !below_target_candidates.is_empty() {
-                                    candidates.push_str(&below_target_candidates);
-                                    candidates.push_str("\n\n");
-                                }
-                                memory_candidates.extend_from_slice(&memory.data);
-                            }
-                        }
+                    let question = current_node.render_question(&variables)?;
+                    let prompt = match &spec.template {
+                        Some(template) => variables.layout(template, &question, primer.as_deref())?,
+                        None => question.to_standard_prompt(primer.as_deref(), variables.examples.as_deref())
                     };
-    
-                    let prompt = current_node
-                        .clone()
-                        .with_context(&context)? 
-                        .with_data(&candidates)?
-                        .question
-                        .unwrap()
-                        .to_standard_prompt(&agent_primer);
-                
+                    
                     log::debug!("\n\n{prompt}");
 
-                    let (thoughts, answer) = text_generator.run(&prompt, disable_thinking)?;
+                    if let Some(model) = current_node.generation().and_then(|generation| generation.model) {
+                        log::info!("{log_id} Node model: {model}");
+                    }
+                    let (thoughts, answer) = models.run(&prompt, current_node.generation(), disable_thinking)?;
                     
                     log::debug!("{thoughts}\n\n");
                     log::debug!("{answer}");
 
+                    let (decision, category) = match spec.parser {
+                        NodeParser::Result => (Self::extract_result(&answer, disable_thinking)?, None),
+                        NodeParser::Category => (None, Self::extract_category(&answer, disable_thinking)?),
+                        NodeParser::Pathogen => {
+                            result.candidates = Self::extract_tags(&answer, "candidate", disable_thinking)?;
+                            result.pathogen = Self::extract_tags(&answer, "pathogen", disable_thinking)?.first().cloned();
+
+                            match &result.pathogen {
+                                None => signals.push(ReviewReason::PathogenMissing),
+                                Some(pathogen) if !data.is_empty() && !Self::is_candidate(pathogen, &data) => {
+                                    log::info!("{log_id} Pathogen not found in candidate taxa: {pathogen}");
+                                    signals.push(ReviewReason::PathogenNotInCandidates)
+                                },
+                                _ => {}
+                            }
+                            (None, None)
+                        },
+                        NodeParser::None => (None, None)
+                    };
 
-                    let candidates = Self::extract_tags(&answer, "candidate", disable_thinking)?;
-                    let pathogens = Self::extract_tags(&answer, "pathogen",disable_thinking)?;
+                    DiagnosticMemory::new(
+                        DiagnosticNode::Generic, 
+                        data, 
+                        decision, 
+                        Some(prompt), 
+                        Some(thoughts), 
+                        Some(answer)
+                    ).with_category(category)
+                }
+            } else {
+                log::info!("{log_id} No data retrieved for this node");
+                spec.empty_memory(&current_node, data)?
+            };
 
-                    result.diagnosis = Diagnosis::Infectious;
-                    result.candidates = candidates;
-                    result.pathogen = pathogens.first().cloned();
+            let action = memory.action();
 
+            self.state.memorize(
+                memory.labelled(&node_label, current_node.check.clone().unwrap_or(DiagnosticNode::Generic))
+            );
 
-                    self.state.memorize(
-                        DiagnosticMemory::new(
-                            DiagnosticNode::DiagnoseInfectious, 
-                            memory_candidates, 
-                            None, 
-                            Some(prompt), 
-                            Some(thoughts), 
-                            Some(answer)
-                        )
-                    );
+            if let Some(diagnosis) = spec.diagnosis {
+                result.diagnosis = diagnosis;
+            }
+            if current_node.final_node.unwrap_or(false) {
+                break
+            }
 
-                    break
-                },
-                Some(DiagnosticNode::DiagnoseNonInfectious) => {
-                    result.diagnosis = Diagnosis::NonInfectious;
-                    break
-                },
-                Some(_) => {
-                    log::warn!("Node processing not implemented for node: {}", current_node.label.unwrap_or("no_label".to_string()));
-                    break
-                }
-                _ => {
-                    log::warn!("Node processing not implemented for node: {}", current_node.label.unwrap_or("no_label".to_string()));
+            match self.get_next_node_label(&current_node, action, &log_id)? {
+                Some(label) => node_label = label,
+                None => {
+                    signals.push(ReviewReason::ExtractionFailed);
                     break
                 }
-            }            
+            }
+        }
+
+        self.tree.review.apply(&mut result, &signals);
+        self.state.prompts.extend(self.prompts.resolved());
+        self.state.diagnosis = Some(result.diagnosis.clone());
+        self.state.review_reason = result.review_reason;
+
+        if let Some(reason) = &result.review_reason {
+            log::info!("{log_id} Review diagnosis: {} ({})", result.diagnosis, serde_plain::to_string(reason).unwrap_or_default());
         }
 
         Ok(result)
     }
-    fn get_next_node_label(&mut self, current_node: &TreeNode, result: Option<bool>, log_id: &str) -> Result<Option<String>, GptError> {
+    fn get_next_node_label(&mut self, current_node: &TreeNode, action: Option<TreeAction>, log_id: &str) -> Result<Option<String>, GptError> {
         
         let node_label = if let Some(next_node_label) = &current_node.next {
             Some(next_node_label.clone())
         } else {
+            // Categories without a branch are treated like a failed extraction
+            let action = match action {
+                Some(TreeAction::Category(category)) if current_node.branch_target(&category).is_none() => {
+                    log::warn!("{log_id} No branch defined for category: {category}");
+                    None
+                },
+                action => action
+            };
             // Failed to extract expected decision value -> repeat node question (self-loop)
-            match result {
+            match action {
                 None => {
                     log::info!("{log_id} Failed to extract decision from answer - intitiate repeat check.");
 
                     // Check the current node repeats before returning
-                    let check_type = current_node.check.as_ref()
-                        .ok_or(GptError::NodeCheckTypeMissing)?;
+                    let label = current_node.label.as_ref()
+                        .ok_or(GptError::TreeNodeLabelMissing)?;
 
                     let count = self
                         .state
                         .repeat
-                        .entry(check_type.clone())
+                        .entry(label.clone())
                         .and_modify(|c| *c += 1)   
                         .or_insert(1);
 
@@ -1939,17 +4548,32 @@ impl DiagnosticAgent {
                         current_node.label.clone()
                     }
                 },
-                Some(decision) => {
-                    if decision {
-                        Some(current_node.true_node.clone().expect(&format!("True node expected")))
-                    } else {
-                        Some(current_node.false_node.clone().expect(&format!("False node expected")))
-                    }
+                Some(action) => {
+                    let target = match &action {
+                        TreeAction::True => current_node.true_node.clone(),
+                        TreeAction::False => current_node.false_node.clone(),
+                        TreeAction::Next => current_node.next.clone(),
+                        TreeAction::Category(category) => current_node.branch_target(category).cloned()
+                    };
+                    Some(target.ok_or(GptError::TreeTransitionMissing(
+                        current_node.label.clone().unwrap_or("no_label".to_string()), 
+                        action.to_string()
+                    ))?)
                 }
             }
         };
         Ok(node_label)
     }
+    fn is_candidate(pathogen: &str, taxa: &[Taxon]) -> bool {
+        let pathogen = pathogen.trim().to_lowercase();
+        taxa.iter().any(|taxon| Self::strip_variant_tags(&taxon.name).to_lowercase() == pathogen)
+    }
+    fn extract_category(s: &str, relaxed: bool) -> Result<Option<String>, GptError> {
+        let categories = Self::extract_tags(s, "category", relaxed)?;
+        
+        // Only consider the first category block
+        Ok(categories.first().map(|category| normalize_category(category.as_str())).filter(|category| !category.is_empty()))
+    }
     fn extract_result(s: &str, relaxed: bool) -> Result<Option<bool>, GptError> {
         let results = Self::extract_tags(s, "result", relaxed)?;
         
@@ -2084,6 +4708,13 @@ impl DiagnosticAgent {
                     graph.add_edge(from, to, TreeEdge::default_next());
                 }
             }
+            for (category, target) in node.branches.iter().flatten() {
+                if let (Some(&from), Some(&to)) =
+                    (node_indices.get(key), node_indices.get(target))
+                {
+                    graph.add_edge(from, to, TreeEdge::category(category));
+                }
+            }
         }
 
         Ok(graph)
@@ -2112,6 +4743,9 @@ impl DiagnosticAgent {
                 if let Some(child) = &node.next {
                     children.push(("next", child));
                 }
+                for (category, child) in node.branches.iter().flatten() {
+                    children.push((category.as_str(), child));
+                }
             }
 
             let count = children.len();
@@ -2146,8 +4780,8 @@ impl DiagnosticAgent {
 
 
 /// Draws a consensus decision tree (as a petgraph) using a hierarchical layout,
-/// scaling node and edge opacities according to frequencies computed from a
-/// collection of trees. If no additional trees are supplied, nodes and edges in the
+/// scaling node and edge opacities (and edge widths) according to frequencies 
+/// computed from a collection of trees. If no additional trees are supplied, nodes and edges in the
 /// consensus tree default to full opacity (alpha = 1).
 ///
 /// # Arguments
@@ -2158,6 +4792,7 @@ impl DiagnosticAgent {
 pub fn draw_consensus_tree(
     consensus_tree: &Graph<TreeNode, TreeEdge>,
     trees: &[Graph<TreeNode, TreeEdge>],
+    root: Option<&str>,
     filename: &str,
     plot_width: u32,
     plot_height: u32,
@@ -2204,27 +4839,33 @@ pub fn draw_consensus_tree(
         graph: &Graph<N, E>,
         node: NodeIndex,
         depth: u32,
-        spacing_x: f64,
-        spacing_y: f64,
+        (spacing_x, spacing_y): (f64, f64),
         x_offset: &mut f64,
         layout: &mut HashMap<NodeIndex, (f64, f64)>,
+        visited: &mut HashSet<NodeIndex>,
     ) {
+        // Nodes are laid out once - repeat edges and cycles are not followed.
+        if !visited.insert(node) {
+            return
+        }
         // Gather children (nodes with outgoing edges).
         let children: Vec<NodeIndex> = graph
             .neighbors_directed(node, Direction::Outgoing)
             .collect();
 
-        if children.is_empty() {
+        // Recursively determine positions for children.
+        for child in &children {
+            layout_tree(graph, *child, depth + 1, (spacing_x, spacing_y), x_offset, layout, visited);
+        }
+        // Children on the current path (cycles) have no position yet.
+        let child_xs: Vec<f64> = children.iter().filter_map(|c| layout.get(c)).map(|(x, _)| *x).collect();
+
+        if child_xs.is_empty() {
             // Leaf: assign current x position and increment the offset.
             layout.insert(node, (*x_offset, depth as f64 * spacing_y));
             *x_offset += spacing_x;
         } else {
-            // Recursively determine positions for children.
-            for child in &children {
-                layout_tree(graph, *child, depth + 1, spacing_x, spacing_y, x_offset, layout);
-            }
             // Position internal node at the horizontal midpoint of its children.
-            let child_xs: Vec<f64> = children.iter().map(|c| layout[c].0).collect();
             let min_x = child_xs.first().cloned().unwrap_or(0.0);
             let max_x = child_xs.last().cloned().unwrap_or(0.0);
             let x = (min_x + max_x) / 2.0;
@@ -2237,14 +4878,17 @@ pub fn draw_consensus_tree(
     let mut layout: HashMap<NodeIndex, (f64, f64)> = HashMap::new();
     let mut x_offset = 20.0; // Starting horizontal offset
 
-    // Identify the root: a node with no incoming edges.
+    // Identify the root: the node with the root label or a node with no incoming edges.
     let root = consensus_tree
         .node_indices()
-        .find(|&n| consensus_tree.neighbors_directed(n, Direction::Incoming).next().is_none())
+        .find(|&n| match root {
+            Some(root) => consensus_tree[n].label.as_deref() == Some(root),
+            None => consensus_tree.neighbors_directed(n, Direction::Incoming).next().is_none()
+        })
         .ok_or(GptError::TreeRootMissing)?;
 
     // Compute the tree layout recursively.
-    layout_tree(consensus_tree, root, 0, spacing_x, spacing_y, &mut x_offset, &mut layout);
+    layout_tree(consensus_tree, root, 0, (spacing_x, spacing_y), &mut x_offset, &mut layout, &mut HashSet::new());
 
     // Center the tree horizontally within the plot.
     let (min_x, max_x, _) = layout.values().fold(
@@ -2265,15 +4909,15 @@ pub fn draw_consensus_tree(
         if let (Some(&(x1, y1)), Some(&(x2, y2))) = (layout.get(&src), layout.get(&tgt)) {
             let src_label = consensus_tree[src].clone();
             let tgt_label = consensus_tree[tgt].clone();
-            let freq = edge_freq.get(&(src_label, tgt_label)).cloned().unwrap_or(1);
+            let freq = edge_freq.get(&(src_label, tgt_label)).cloned().unwrap_or(0);
             let alpha = (freq as f64) / (max_edge_freq as f64);
-            let edge_color = RGBAColor(50, 50, 200, alpha);
+            let edge_color = RGBAColor(50, 50, 200, alpha.max(0.1)); // untraversed edges remain visible
             drawing_area.draw(&PathElement::new(
                 vec![(x1 as i32, y1 as i32), (x2 as i32, y2 as i32)],
                 ShapeStyle {
                     color: edge_color,
                     filled: false,
-                    stroke_width: 2,
+                    stroke_width: 1 + (alpha * 7.0).round() as u32,
                 },
             ))?;
         }
@@ -2281,9 +4925,9 @@ pub fn draw_consensus_tree(
 
     for node in consensus_tree.node_indices() {
         if let Some(&(x, y)) = layout.get(&node) {
-            let freq = node_freq.get(&consensus_tree[node]).cloned().unwrap_or(1);
+            let freq = node_freq.get(&consensus_tree[node]).cloned().unwrap_or(0);
             let alpha = (freq as f64) / (max_node_freq as f64);
-            let node_color = RGBAColor(200, 50, 50, alpha);
+            let node_color = RGBAColor(200, 50, 50, alpha.max(0.1));
             drawing_area.draw(&Circle::new((x as i32, y as i32), 10, ShapeStyle::from(&node_color).filled()))?;
             drawing_area.draw(&Text::new(
                 format!("{}", match &consensus_tree[node].label { Some(label) => label.as_str(), None => "no_label" }),
@@ -2296,4 +4940,669 @@ pub fn draw_consensus_tree(
     drawing_area.present()?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Temporary directory of a test - each call gets its own directory as tests run in parallel
+    fn temp_dir(name: &str) -> PathBuf {
+        static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("meta-gpt-{name}-{}-{count}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    const INCLUDING_TREE: &str = r#"
+name: main
+version: 0.1.0
+description: Tree with a screening sub-tree
+max_repeats: 3
+root: start
+includes:
+  - name: screen
+    file: screen.yaml
+    exits:
+      positive: diagnose_infectious
+      negative: diagnose_non_infectious
+nodes:
+  start:
+    label: start
+    check: above_threshold_query
+    true_node: diagnose_infectious
+    false_node: screen
+  diagnose_infectious:
+    label: diagnose_infectious
+    check: diagnose_infectious
+    final_node: true
+  diagnose_non_infectious:
+    label: diagnose_non_infectious
+    check: diagnose_non_infectious
+    final_node: true
+"#;
+
+    const SUBTREE: &str = r#"
+name: screen
+version: 0.1.0
+description: Screening sub-tree
+max_repeats: 3
+nodes:
+  check:
+    label: check
+    check: below_threshold_query
+    true_node: "@positive"
+    false_node: "@negative"
+"#;
+
+    fn write_include_trees(name: &str, tree: &str) -> PathBuf {
+        let dir = temp_dir(name);
+        std::fs::write(dir.join("main.yaml"), tree).unwrap();
+        std::fs::write(dir.join("screen.yaml"), SUBTREE).unwrap();
+        dir.join("main.yaml")
+    }
+
+    #[test]
+    fn includes_are_expanded_into_namespaced_nodes() {
+        let tree = DecisionTree::from_file(write_include_trees("include", INCLUDING_TREE)).unwrap();
+
+        assert!(tree.includes.is_empty());
+        assert_eq!(tree.nodes["start"].false_node.as_deref(), Some("screen.check"));
+
+        let check = &tree.nodes["screen.check"];
+        assert_eq!(check.label.as_deref(), Some("screen.check"));
+        assert_eq!(check.true_node.as_deref(), Some("diagnose_infectious"));
+        assert_eq!(check.false_node.as_deref(), Some("diagnose_non_infectious"));
+    }
+
+    #[test]
+    fn include_without_exit_target_fails() {
+        let tree = INCLUDING_TREE.replace("      negative: diagnose_non_infectious\n", "");
+        let result = DecisionTree::from_file(write_include_trees("include-exit", &tree));
+        assert!(matches!(result, Err(GptError::TreeIncludeExitMissing(name, exit)) if name == "screen" && exit == "negative"));
+    }
+
+    #[test]
+    fn include_name_of_existing_node_fails() {
+        let tree = INCLUDING_TREE.replace("  - name: screen", "  - name: start");
+        let result = DecisionTree::from_file(write_include_trees("include-duplicate", &tree));
+        assert!(matches!(result, Err(GptError::TreeNodeDuplicate(label)) if label == "start"));
+    }
+
+    fn prompt_library(name: &str, prompts: &[(&str, &str)]) -> Result<PromptLibrary, GptError> {
+        let dir = temp_dir(name);
+        std::fs::create_dir_all(dir.join("tasks")).unwrap();
+        for (file, prompt) in prompts {
+            std::fs::write(dir.join(file), prompt).unwrap();
+        }
+        PromptLibrary::from_dir(&dir)
+    }
+
+    #[test]
+    fn library_prompts_replace_builtin_prompts() {
+        let prompts = prompt_library("prompts", &[
+            ("tasks/diagnose.yaml", "id: task.diagnose_default\nversion: '2.1'\ntext: Library task\n")
+        ]).unwrap();
+
+        assert_eq!(NodeTask::DiagnoseDefault.prompt_id(), "task.diagnose_default");
+        assert_eq!(prompts.resolve(&NodeTask::DiagnoseDefault), "Library task");
+        assert_eq!(prompts.resolve(&NodeTask::DiagnoseSimple), NodeTask::DiagnoseSimple.builtin_text());
+
+        let resolved = prompts.resolved();
+        assert_eq!(resolved["task.diagnose_default"], "2.1");
+        assert_eq!(resolved["task.diagnose_simple"], "builtin");
+    }
+
+    #[test]
+    fn duplicate_library_prompts_fail() {
+        let prompt = "id: task.diagnose_default\nversion: '1'\ntext: Library task\n";
+        let result = prompt_library("prompts-duplicate", &[("a.yaml", prompt), ("tasks/b.yaml", prompt)]);
+        assert!(matches!(result, Err(GptError::PromptDuplicate(id)) if id == "task.diagnose_default"));
+    }
+
+    fn clinical_record(json: &str) -> ClinicalRecord {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn clinical_record_in_range_is_valid() {
+        let record = clinical_record(r#"{
+            "age_band": "adult",
+            "symptoms": ["fever", "headache"],
+            "csf": {"white_cells": 250, "protein": 1.2, "glucose": 2.1},
+            "travel": "Southeast Asia"
+        }"#);
+        assert!(record.validate().is_ok());
+    }
+
+    #[test]
+    fn clinical_record_problems_are_reported_together() {
+        let record = clinical_record(r#"{
+            "symptoms": ["fever", " "],
+            "csf": {"protein": 120.0, "glucose": -1.0},
+            "notes": ""
+        }"#);
+        let Err(GptError::ClinicalRecordInvalid(problems)) = record.validate() else {
+            panic!("expected an invalid clinical record")
+        };
+        assert!(problems.contains("CSF protein out of range"));
+        assert!(problems.contains("CSF glucose out of range"));
+        assert!(problems.contains("blank symptom or antimicrobial"));
+        assert!(problems.contains("blank notes"));
+        assert!(!problems.contains("white cells"));
+    }
+
+    #[test]
+    fn ablated_clinical_fields_are_not_rendered() {
+        let record = clinical_record(r#"{
+            "immune_status": "transplant",
+            "symptoms": ["fever"],
+            "csf": {"white_cells": 40},
+            "antimicrobials": ["ceftriaxone"]
+        }"#);
+        let ablated = record.clone().ablate(&[ClinicalField::Csf, ClinicalField::Antimicrobials]);
+
+        assert!(record.to_str().contains("CSF: white cells 40 cells/uL"));
+        assert_eq!(ablated.to_str(), "Immune status: transplant\nSymptoms: fever");
+        assert!(record.ablate(&[ClinicalField::ImmuneStatus, ClinicalField::Symptoms, ClinicalField::Csf, ClinicalField::Antimicrobials]).is_empty());
+    }
+
+    fn example_library(name: &str) -> ExampleLibrary {
+        let dir = temp_dir(name);
+        std::fs::create_dir_all(dir.join("csf")).unwrap();
+        std::fs::write(dir.join("hsv.yaml"), "id: hsv\nsample_types: [csf]\ndata: HSV-1\nanswer: infectious\n").unwrap();
+        std::fs::write(dir.join("csf/cutibacterium.yaml"), "id: cutibacterium\nsample_types: [csf]\ndata: Cutibacterium acnes\nanswer: non-infectious\n").unwrap();
+        std::fs::write(dir.join("csf/pneumococcus.json"), r#"{"id": "pneumococcus", "sample_types": ["csf", "blood"], "data": "Streptococcus pneumoniae", "answer": "infectious"}"#).unwrap();
+        std::fs::write(dir.join("README.md"), "not an example").unwrap();
+        ExampleLibrary::from_dir(&dir).unwrap()
+    }
+
+    fn selected_ids(examples: Vec<&FewShotExample>) -> Vec<&str> {
+        examples.into_iter().map(|example| example.id.as_str()).collect()
+    }
+
+    #[test]
+    fn referenced_examples_are_selected_before_sample_type_examples() {
+        let library = example_library("examples");
+        let node = NodeExamples { ids: vec!["pneumococcus".to_string()], sample_type: true, max: None };
+
+        assert_eq!(selected_ids(library.select(&node, Some("csf")).unwrap()), ["pneumococcus", "cutibacterium", "hsv"]);
+        assert_eq!(selected_ids(library.select(&node, Some("blood")).unwrap()), ["pneumococcus"]);
+        assert_eq!(selected_ids(library.select(&node, None).unwrap()), ["pneumococcus"]);
+
+        let node = NodeExamples { max: Some(2), ..node };
+        assert_eq!(selected_ids(library.select(&node, Some("csf")).unwrap()), ["pneumococcus", "cutibacterium"]);
+    }
+
+    #[test]
+    fn missing_example_references_fail() {
+        let library = example_library("examples-missing");
+        let node = NodeExamples { ids: vec!["hsv".to_string(), "vzv".to_string()], sample_type: false, max: None };
+        assert!(matches!(library.select(&node, None), Err(GptError::ExampleMissing(id)) if id == "vzv"));
+
+        let tree = INCLUDING_TREE.replace(
+            "    check: diagnose_infectious\n", "    check: diagnose_infectious\n    examples:\n      ids: [hsv, vzv]\n"
+        );
+        let tree = DecisionTree::from_file(write_include_trees("examples-tree", &tree)).unwrap();
+        assert!(matches!(library.check(&tree), Err(GptError::ExampleMissing(id)) if id == "vzv (node: diagnose_infectious)"));
+    }
+
+    fn taxa_columns() -> Vec<(String, Vec<serde_json::Value>)> {
+        vec![
+            ("name".to_string(), vec![serde_json::json!("Human betaherpesvirus 5"), serde_json::json!("Escherichia|coli")]),
+            ("rpm_kraken2".to_string(), vec![serde_json::json!(12.5), serde_json::Value::Null]),
+            ("contigs".to_string(), vec![serde_json::json!(3), serde_json::json!(0)])
+        ]
+    }
+
+    fn taxa_renderer(format: TaxaFormat) -> TaxaRenderer {
+        TaxaRenderer { format, ..Default::default() }
+    }
+
+    #[test]
+    fn taxa_are_tabulated_in_markdown() {
+        let table = taxa_renderer(TaxaFormat::Markdown).tabulate(&taxa_columns(), 2).unwrap();
+        assert_eq!(table, [
+            "| name | rpm_kraken2 | contigs |",
+            "|---|---|---|",
+            "| Human betaherpesvirus 5 | 12.5 | 3 |",
+            "| Escherichia\\|coli | - | 0 |"
+        ].join("\n"));
+    }
+
+    #[test]
+    fn taxa_are_tabulated_in_csv_and_json() {
+        let csv = taxa_renderer(TaxaFormat::Csv).tabulate(&taxa_columns(), 2).unwrap();
+        assert_eq!(csv, "name,rpm_kraken2,contigs\nHuman betaherpesvirus 5,12.5,3\nEscherichia|coli,,0");
+
+        let json = taxa_renderer(TaxaFormat::Json).tabulate(&taxa_columns(), 2).unwrap();
+        let records: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(records[0]["rpm_kraken2"], serde_json::json!(12.5));
+        assert_eq!(records[1]["rpm_kraken2"], serde_json::Value::Null);
+        assert_eq!(records[1]["name"], serde_json::json!("Escherichia|coli"));
+    }
+
+    #[test]
+    fn empty_taxa_render_the_header_without_tool_columns() {
+        let table = taxa_renderer(TaxaFormat::Markdown).table(&[], &BTreeMap::new()).unwrap();
+        assert_eq!(table, "| name | domain | contigs | bases |\n|---|---|---|---|");
+    }
+
+    #[test]
+    fn prose_is_not_a_tabular_format() {
+        let result = taxa_renderer(TaxaFormat::Prose).table(&[], &BTreeMap::new());
+        assert!(matches!(result, Err(GptError::TaxaFormatNotTabular(format)) if format == "prose"));
+    }
+
+    const CONTAMINANTS: &str = r#"
+- taxon: Ralstonia pickettii
+  source: kit
+  assays: [cns-dna]
+  sample_types: [csf]
+  rpm: {low: 0, high: 15, median: 2.5, samples: 120}
+- taxon: "1747"
+  source: skin
+  note: common after lumbar puncture
+"#;
+
+    fn contaminants() -> Vec<ContaminantEntry> {
+        serde_yaml::from_str(CONTAMINANTS).unwrap()
+    }
+
+    #[test]
+    fn contaminants_match_by_taxid_or_name_within_scope() {
+        let entries = contaminants();
+        let (ralstonia, cutibacterium) = (&entries[0], &entries[1]);
+
+        assert!(ralstonia.matches("329", "ralstonia PICKETTII", Some("cns-dna"), Some("csf")));
+        assert!(!ralstonia.matches("329", "Ralstonia pickettii", Some("cns-rna"), Some("csf")));
+        assert!(!ralstonia.matches("329", "Ralstonia pickettii", Some("cns-dna"), Some("blood")));
+        assert!(!ralstonia.matches("329", "Ralstonia pickettii", None, Some("csf")));
+        assert!(!ralstonia.matches("329", "Ralstonia mannitolilytica", Some("cns-dna"), Some("csf")));
+
+        assert!(cutibacterium.matches("1747", "Cutibacterium acnes", None, None));
+        assert!(cutibacterium.matches("1747", "Propionibacterium acnes", Some("cns-rna"), Some("blood")));
+        assert!(!cutibacterium.matches("1748", "Cutibacterium granulosum", None, None));
+    }
+
+    #[test]
+    fn contaminant_annotations_flag_observed_rpm_above_the_typical_range() {
+        let entries = contaminants();
+
+        assert_eq!(entries[0].annotate(15.0), "known extraction-kit contaminant, typical RPM 0-15 (median 2.5, n = 120)");
+        assert_eq!(
+            entries[0].annotate(40.0), 
+            "known extraction-kit contaminant, typical RPM 0-15 (median 2.5, n = 120), observed above the typical range"
+        );
+        assert_eq!(entries[1].annotate(1000.0), "known skin flora contaminant, common after lumbar puncture");
+    }
+
+    #[test]
+    fn mermaid_ids_are_unique_and_not_reserved() {
+        let ids = mermaid_ids(["screen.check", "screen_check", "end", "End", "screen.check"].into_iter());
+
+        assert_eq!(ids.len(), 4);
+        assert_eq!(ids["screen.check"], "screen_check");
+        assert_eq!(ids["screen_check"], "screen_check_1");
+        assert_eq!(ids["end"], "end_1");
+        assert_eq!(ids["End"], "End_1");
+    }
+
+    fn reviewed(policy: &ReviewPolicy, diagnosis: Diagnosis, signals: &[ReviewReason]) -> (Diagnosis, Option<ReviewReason>) {
+        let mut result = DiagnosticResult { diagnosis, ..DiagnosticResult::non_infectious() };
+        policy.apply(&mut result, signals);
+        (result.diagnosis, result.review_reason)
+    }
+
+    #[test]
+    fn default_review_policy_keeps_the_diagnosis() {
+        let policy = ReviewPolicy::default();
+        let signals = [ReviewReason::ConflictingTiers, ReviewReason::PathogenMissing, ReviewReason::PathogenNotInCandidates];
+
+        assert_eq!(reviewed(&policy, Diagnosis::Infectious, &signals), (Diagnosis::Infectious, None));
+        assert_eq!(reviewed(&policy, Diagnosis::NonInfectious, &signals), (Diagnosis::NonInfectious, None));
+    }
+
+    #[test]
+    fn failed_extraction_is_recorded_for_the_unknown_diagnosis() {
+        let policy = ReviewPolicy::default();
+        let signals = [ReviewReason::ConflictingTiers, ReviewReason::ExtractionFailed];
+        assert_eq!(reviewed(&policy, Diagnosis::Unknown, &signals), (Diagnosis::Unknown, Some(ReviewReason::ExtractionFailed)));
+        assert_eq!(reviewed(&policy, Diagnosis::Unknown, &[]), (Diagnosis::Unknown, None));
+
+        let policy = ReviewPolicy { extraction_failed: ReviewOutcome::NonInfectiousReview, ..Default::default() };
+        assert_eq!(reviewed(&policy, Diagnosis::Unknown, &signals), (Diagnosis::NonInfectiousReview, Some(ReviewReason::ExtractionFailed)));
+    }
+
+    #[test]
+    fn first_signal_with_an_effect_is_the_review_reason() {
+        let policy = ReviewPolicy { 
+            pathogen_not_in_candidates: ReviewOutcome::Review, 
+            conflicting_tiers: ReviewOutcome::Review, 
+            ..Default::default() 
+        };
+        let signals = [ReviewReason::PathogenMissing, ReviewReason::ConflictingTiers, ReviewReason::PathogenNotInCandidates];
+
+        assert_eq!(reviewed(&policy, Diagnosis::Infectious, &signals), (Diagnosis::InfectiousReview, Some(ReviewReason::ConflictingTiers)));
+        assert_eq!(reviewed(&policy, Diagnosis::NonInfectious, &signals), (Diagnosis::NonInfectiousReview, Some(ReviewReason::ConflictingTiers)));
+        assert_eq!(reviewed(&policy, Diagnosis::Tumor, &signals), (Diagnosis::Tumor, None));
+    }
+
+    const REPEAT_TREE: &str = r#"
+name: repeat
+version: 0.1.0
+description: Tree with a single query node
+max_repeats: 2
+nodes:
+  query:
+    label: query
+    check: above_threshold_query
+    true_node: infectious
+    branches:
+      bacterial: infectious
+  infectious:
+    label: infectious
+    check: diagnose_infectious
+    final_node: true
+"#;
+
+    #[test]
+    fn failed_extraction_repeats_the_node_until_max_repeats() {
+        let mut agent = DiagnosticAgent::from_tree(serde_yaml::from_str(REPEAT_TREE).unwrap()).unwrap();
+        let node = agent.tree.nodes["query"].clone();
+
+        assert_eq!(agent.get_next_node_label(&node, None, "[test]").unwrap().as_deref(), Some("query"));
+        assert_eq!(agent.get_next_node_label(&node, Some(TreeAction::Category("viral".to_string())), "[test]").unwrap().as_deref(), Some("query"));
+        assert_eq!(agent.get_next_node_label(&node, None, "[test]").unwrap(), None);
+        assert_eq!(agent.state.repeat["query"], 3);
+
+        assert_eq!(agent.get_next_node_label(&node, Some(TreeAction::True), "[test]").unwrap().as_deref(), Some("infectious"));
+        assert_eq!(agent.get_next_node_label(&node, Some(TreeAction::Category("bacterial".to_string())), "[test]").unwrap().as_deref(), Some("infectious"));
+        assert!(matches!(
+            agent.get_next_node_label(&node, Some(TreeAction::False), "[test]"), 
+            Err(GptError::TreeTransitionMissing(label, _)) if label == "query"
+        ));
+    }
+
+    #[test]
+    fn interrupted_path_shows_the_diagnosis_on_the_last_node() {
+        let tree: DecisionTree = serde_yaml::from_str(REPEAT_TREE).unwrap();
+        let mut state = AgentState::new();
+        for _ in 0..3 {
+            state.memorize(DiagnosticMemory::new(DiagnosticNode::Generic, vec![], None, None, None, None).labelled("query", DiagnosticNode::Generic));
+        }
+        state.diagnosis = Some(Diagnosis::Unknown);
+        state.review_reason = Some(ReviewReason::ExtractionFailed);
+
+        let overlay = PathOverlay::new(&tree, &state, None);
+        assert_eq!(overlay.path, ["query"]);
+        assert_eq!(overlay.final_node, None);
+        assert_eq!(overlay.diagnosis_text("query").as_deref(), Some("diagnosis: unknown (review: extraction_failed)"));
+        assert_eq!(overlay.diagnosis_text("infectious"), None);
+
+        let mermaid = tree.to_mermaid_overlay(&overlay).unwrap();
+        assert!(mermaid.contains("repeats: 2<br/>diagnosis: unknown (review: extraction_failed)"));
+        assert!(mermaid.contains("class query diagnosis"));
+    }
+
+    fn visited(labels: &[&str], diagnosis: Diagnosis, review_reason: Option<ReviewReason>) -> AgentState {
+        let mut state = AgentState::new();
+        for label in labels {
+            state.memorize(DiagnosticMemory::new(DiagnosticNode::Generic, vec![], None, None, None, None).labelled(label, DiagnosticNode::Generic));
+        }
+        state.diagnosis = Some(diagnosis);
+        state.review_reason = review_reason;
+        state
+    }
+
+    #[test]
+    fn cohort_paths_are_counted_and_drawn_on_trees_with_repeat_edges() {
+        let tree: DecisionTree = serde_yaml::from_str(&REPEAT_TREE.replace("    true_node: infectious\n", "    true_node: infectious\n    false_node: query\n")).unwrap();
+        let cohort = CohortPaths {
+            samples: [
+                ("a", visited(&["query", "query", "infectious"], Diagnosis::Infectious, None)),
+                ("b", visited(&["query", "infectious"], Diagnosis::Infectious, None)),
+                ("c", visited(&["query", "query", "query"], Diagnosis::Unknown, Some(ReviewReason::ExtractionFailed)))
+            ].into_iter().map(|(sample, state)| (sample.to_string(), PathOverlay::new(&tree, &state, None))).collect()
+        };
+
+        let frequencies = cohort.path_frequencies();
+        assert_eq!(frequencies.len(), 2);
+        assert_eq!((frequencies[0].path.as_str(), frequencies[0].diagnosis.as_str(), frequencies[0].samples), ("query -> infectious", "infectious", 2));
+        assert_eq!((frequencies[1].path.as_str(), frequencies[1].review_reason.as_deref()), ("query", Some("extraction_failed")));
+
+        let svg = temp_dir("consensus").join("consensus.svg");
+        let graph = DiagnosticAgent::graph(&tree).unwrap();
+        draw_consensus_tree(&graph, &cohort.graphs(&tree).unwrap(), Some("query"), svg.to_str().unwrap(), 400, 300).unwrap();
+        assert!(std::fs::read_to_string(svg).unwrap().contains("infectious"));
+    }
+
+    #[test]
+    fn unknown_clinical_record_fields_fail() {
+        assert!(serde_json::from_str::<ClinicalRecord>(r#"{"immune_satus": "transplant"}"#).is_err());
+        assert!(serde_json::from_str::<ClinicalRecord>(r#"{"csf": {"white_cell": 40}}"#).is_err());
+    }
+
+    #[test]
+    fn sample_sheet_values_are_read_as_records_or_notes() {
+        let path = temp_dir("sample-sheet").join("samples.tsv");
+        std::fs::write(&path, [
+            "sample\tclinical",
+            "s1\t  {\"symptoms\": [\"fever\"], \"travel\": \"Ghana\"}",
+            "s2\tFever after travel",
+            "s3\t "
+        ].join("\n")).unwrap();
+
+        let Ok(ClinicalContext::Record(record)) = ClinicalContext::from_sample_sheet(&path, "s1", "clinical") else {
+            panic!("expected a clinical record")
+        };
+        assert_eq!(record.to_str(), "Symptoms: fever\nTravel: Ghana");
+        assert!(matches!(ClinicalContext::from_sample_sheet(&path, "s2", "clinical"), Ok(ClinicalContext::Custom(notes)) if notes == "Fever after travel"));
+        assert!(matches!(ClinicalContext::from_sample_sheet(&path, "s3", "clinical"), Ok(ClinicalContext::None)));
+        assert!(matches!(ClinicalContext::from_sample_sheet(&path, "s4", "clinical"), Err(GptError::SampleSheetSampleMissing(_))));
+    }
+
+    #[test]
+    fn tree_description_changes_are_tree_level() {
+        let before: DecisionTree = serde_yaml::from_str(REPEAT_TREE).unwrap();
+        let after: DecisionTree = serde_yaml::from_str(&REPEAT_TREE.replace("Tree with a single query node", "Tree with one query node")).unwrap();
+
+        let changes = before.diff(&after).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].kind, changes[0].node.as_deref(), changes[0].field.as_str()), (TreeChangeKind::TreeChanged, None, "description"));
+        assert!(changes[0].diff.as_deref().is_some_and(|diff| diff.starts_with("--- description (before)")));
+    }
+
+    #[test]
+    fn empty_candidate_blocks_keep_the_heading_in_every_format() {
+        for format in [TaxaFormat::Prose, TaxaFormat::Markdown, TaxaFormat::Csv, TaxaFormat::Json] {
+            let renderer = taxa_renderer(format);
+            let primary = CandidateLabel::Primary.render_with(vec![], &renderer, &BTreeMap::new()).unwrap();
+            let custom = CandidateLabel::Custom("Viral taxa".to_string()).render_with(vec![], &renderer, &BTreeMap::new()).unwrap();
+
+            assert_eq!(primary, "Primary threshold taxa:\n\nNo taxa detected.");
+            assert_eq!(custom, "Viral taxa:\n\nNo taxa detected.");
+        }
+    }
+
+    fn memory(label: &str, result: Option<bool>) -> DiagnosticMemory {
+        DiagnosticMemory::new(DiagnosticNode::Generic, vec![], result, None, None, None).labelled(label, DiagnosticNode::Generic)
+    }
+
+    #[test]
+    fn node_inputs_are_tiers_followed_by_recalled_memories() {
+        let mut state = AgentState::new();
+        state.memorize(memory("secondary", Some(false)));
+        state.memorize(memory("target", Some(true)));
+        state.memorize(memory("secondary", Some(true)));
+
+        let spec = NodeSpec {
+            memories: [("secondary", CandidateLabel::Secondary, true), ("target", CandidateLabel::Target, false), ("primary", CandidateLabel::Primary, false)]
+                .into_iter()
+                .map(|(node, label, positive)| MemoryInput { node: node.to_string(), label, positive })
+                .collect(),
+            ..NodeSpec::default().tiers(vec![DataTier::Primary])
+        };
+        let inputs = state.node_inputs(&spec, &[(DataTier::Primary, vec![])]);
+
+        let labels: Vec<(CandidateLabel, Option<bool>)> = inputs.iter().map(|(label, _, memory)| (label.clone(), memory.as_ref().and_then(|memory| memory.result))).collect();
+        assert_eq!(labels, [(CandidateLabel::Primary, None), (CandidateLabel::Secondary, Some(true)), (CandidateLabel::Target, Some(true))]);
+
+        let state = AgentState { memory: vec![memory("secondary", Some(false))], ..AgentState::new() };
+        assert_eq!(state.node_inputs(&spec, &[]).len(), 0);
+    }
+
+    #[test]
+    fn single_memory_input_with_data_is_reused() {
+        let input = |taxa: usize, memory: Option<DiagnosticMemory>| (CandidateLabel::Secondary, vec!["taxon"; taxa], memory);
+
+        let inputs = [input(0, Some(memory("secondary", Some(false)))), input(2, Some(memory("target", Some(true))))];
+        assert_eq!(single_memory_input(&inputs).and_then(|memory| memory.label.as_deref()), Some("target"));
+
+        let inputs = [input(1, Some(memory("secondary", Some(false)))), input(2, Some(memory("target", Some(true))))];
+        assert!(single_memory_input(&inputs).is_none());
+
+        let inputs = [input(2, None), input(0, Some(memory("target", Some(true))))];
+        assert!(single_memory_input(&inputs).is_none());
+    }
+
+    #[test]
+    fn only_memory_inputs_with_data_conflict() {
+        let input = |taxa: usize, result: Option<bool>| (CandidateLabel::Secondary, vec!["taxon"; taxa], Some(memory("memory", result)));
+
+        assert!(conflicting_inputs(&[input(1, Some(true)), input(3, Some(false))]));
+        assert!(!conflicting_inputs(&[input(1, Some(true)), input(0, Some(false))]));
+        assert!(!conflicting_inputs(&[input(1, Some(true)), input(2, None), input(1, Some(true))]));
+        assert!(!conflicting_inputs(&[input(1, Some(true)), (CandidateLabel::Primary, vec!["taxon"], None)]));
+    }
+
+    const CATEGORY_TREE: &str = r#"
+name: triage
+version: 0.1.0
+description: Tree with a category node
+max_repeats: 1
+nodes:
+  triage:
+    label: triage
+    question: Which kind of infection is supported by the data?
+    spec:
+      tiers: [primary]
+      parser: category
+      empty_category: no taxa
+    branches:
+      Viral infection: infectious
+      bacterial-infection: infectious
+      no-taxa: non_infectious
+  infectious:
+    label: infectious
+    check: diagnose_infectious
+    final_node: true
+  non_infectious:
+    label: non_infectious
+    check: diagnose_non_infectious
+    final_node: true
+"#;
+
+    fn missing_branches(tree: &str) -> Vec<String> {
+        let tree: DecisionTree = serde_yaml::from_str(tree).unwrap();
+        tree.validate().unwrap().into_iter()
+            .filter(|issue| issue.kind == TreeIssueKind::MissingBranch)
+            .map(|issue| issue.message)
+            .collect()
+    }
+
+    #[test]
+    fn category_answers_are_normalized_to_branches() {
+        let answer = "<category> Viral   Infection </category> <category>bacterial infection</category>";
+        assert_eq!(DiagnosticAgent::extract_category(answer, false).unwrap().as_deref(), Some("viral-infection"));
+        assert_eq!(DiagnosticAgent::extract_category("<category> </category>", false).unwrap(), None);
+
+        let mut agent = DiagnosticAgent::from_tree(serde_yaml::from_str(CATEGORY_TREE).unwrap()).unwrap();
+        let node = agent.tree.nodes["triage"].clone();
+        for category in ["viral-infection", "Bacterial Infection"] {
+            let action = Some(TreeAction::Category(category.to_string()));
+            assert_eq!(agent.get_next_node_label(&node, action, "[test]").unwrap().as_deref(), Some("infectious"));
+        }
+    }
+
+    #[test]
+    fn category_nodes_without_data_take_the_empty_category_branch() {
+        let mut agent = DiagnosticAgent::from_tree(serde_yaml::from_str(CATEGORY_TREE).unwrap()).unwrap();
+        let node = agent.tree.nodes["triage"].clone();
+        let spec = node.node_spec().unwrap();
+
+        let memory = spec.empty_memory(&node, vec![]).unwrap();
+        assert_eq!((memory.result, memory.category.as_deref()), (None, Some("no taxa")));
+        assert_eq!(agent.get_next_node_label(&node, memory.action(), "[test]").unwrap().as_deref(), Some("non_infectious"));
+
+        let spec = NodeSpec { empty_category: None, ..spec };
+        assert!(matches!(spec.empty_memory(&node, vec![]), Err(GptError::TreeTransitionMissing(label, _)) if label == "triage"));
+
+        let spec = NodeSpec { parser: NodeParser::Result, ..spec };
+        assert!(matches!(spec.empty_memory(&node, vec![]).unwrap().action(), Some(TreeAction::False)));
+    }
+
+    #[test]
+    fn category_nodes_require_a_reachable_empty_category() {
+        assert!(missing_branches(CATEGORY_TREE).is_empty());
+        assert_eq!(
+            missing_branches(&CATEGORY_TREE.replace("      empty_category: no taxa\n", "")), 
+            ["category node requires an empty_category for samples without data"]
+        );
+        assert_eq!(
+            missing_branches(&CATEGORY_TREE.replace("empty_category: no taxa", "empty_category: unknown")), 
+            ["empty_category has no branch target: unknown"]
+        );
+        assert!(missing_branches(&CATEGORY_TREE.replace("      empty_category: no taxa\n", "      empty_category: no taxa\n      prompt_empty: true\n")).is_empty());
+    }
+
+    fn rule(yaml: &str) -> NodeRule {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn taxon_conditions_require_all_specified_conditions() {
+        let condition = rule("name: viral\nmatch: any\ncondition: {domains: [Viruses], min_rpm: 10, min_contigs: 1}").condition;
+        let phages = vec!["Escherichia virus T4".to_string()];
+
+        assert!(condition.holds("Human betaherpesvirus 5", Some("Viruses"), &[(4.0, 20, 0), (12.0, 30, 2)], &phages));
+        assert!(condition.holds("Human betaherpesvirus 5", Some("Viruses"), &[(4.0, 20, 2), (12.0, 30, 0)], &[]));
+        assert!(!condition.holds("Human betaherpesvirus 5", Some("Viruses"), &[(12.0, 30, 0)], &[]));
+        assert!(!condition.holds("Human betaherpesvirus 5", Some("Viruses"), &[(9.9, 20, 2)], &phages));
+        assert!(!condition.holds("Streptococcus pneumoniae", Some("Bacteria"), &[(40.0, 200, 3)], &phages));
+        assert!(!condition.holds("Human betaherpesvirus 5", None, &[(40.0, 200, 3)], &phages));
+        assert!(!condition.holds("Human betaherpesvirus 5", Some("Viruses"), &[], &phages));
+
+        let phage = rule("name: phage\nmatch: any\ncondition: {phage: true}").condition;
+        assert!(phage.holds("Escherichia virus T4", Some("Viruses"), &[], &phages));
+        assert!(phage.holds("Enterobacteria phage lambda", Some("Viruses"), &[], &[]));
+        assert!(!phage.holds("Human betaherpesvirus 5", Some("Viruses"), &[], &phages));
+        assert!(!phage.holds("Escherichia virus T4", Some("Bacteria"), &[], &phages));
+
+        let named = rule("name: named\nmatch: any\ncondition: {names: [Cutibacterium acnes], min_reads: 5}").condition;
+        assert!(named.holds("Cutibacterium acnes", Some("Bacteria"), &[(0.1, 5, 0)], &[]));
+        assert!(!named.holds("Cutibacterium acnes", Some("Bacteria"), &[(0.1, 4, 0)], &[]));
+        assert!(!named.holds("Cutibacterium granulosum", Some("Bacteria"), &[(0.1, 5, 0)], &[]));
+    }
+
+    #[test]
+    fn rule_quantifiers_over_taxon_matches() {
+        let [any, all, none] = ["any", "all", "none"].map(|quantifier| rule(&format!("name: {quantifier}\nmatch: {quantifier}")));
+
+        assert!(any.quantify([false, true].into_iter()));
+        assert!(!any.quantify([false, false].into_iter()));
+        assert!(!any.quantify(std::iter::empty()));
+
+        assert!(all.quantify([true, true].into_iter()));
+        assert!(!all.quantify([true, false].into_iter()));
+        assert!(!all.quantify([false, true].into_iter()));
+        assert!(!all.quantify(std::iter::empty()));
+
+        assert!(none.quantify([false, false].into_iter()));
+        assert!(!none.quantify([false, true].into_iter()));
+        assert!(none.quantify(std::iter::empty()));
+        assert!(none.evaluate(&[], &[]));
+    }
+}
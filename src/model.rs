@@ -1,9 +1,459 @@
 
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, LazyLock, Mutex};
 use clap::ValueEnum;
-use hf_hub::api::sync::Api;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use hf_hub::api::sync::{Api, ApiBuilder};
+use serde::{Deserialize, Serialize};
 
-use crate::{error::GptError, utils::TokenOutputStream};
+use crate::{error::GptError, utils::{sha256_file, TokenOutputStream}};
+
+
+/// Lock manifest written into the model directory on download
+pub const MODEL_MANIFEST: &str = "meta-gpt.lock.json";
+
+/// Serializes lock manifest updates from concurrent downloads
+static MANIFEST_LOCK: Mutex<()> = Mutex::new(());
+
+type FetchLocks = HashMap<(String, String), Arc<Mutex<()>>>;
+
+/// Serializes concurrent fetches of the same repository file - models can share 
+/// a tokenizer repository and concurrent downloads of the same file contend for 
+/// the blob lock of the Huggingface cache
+static FETCH_LOCKS: LazyLock<Mutex<FetchLocks>> = LazyLock::new(Default::default);
+
+fn fetch_lock(repository: &str, file: &str) -> Arc<Mutex<()>> {
+    let mut locks = FETCH_LOCKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    locks.entry((repository.to_string(), file.to_string())).or_default().clone()
+}
+
+/// Huggingface API client using `HF_ENDPOINT` or a custom compatible mirror endpoint
+fn huggingface_api(endpoint: Option<&str>, retries: usize) -> Result<Api, GptError> {
+    let builder = ApiBuilder::from_env()
+        .with_retries(retries)
+        .with_progress(false);
+    let builder = match endpoint {
+        Some(endpoint) => builder.with_endpoint(endpoint.trim_end_matches('/').to_string()),
+        None => builder
+    };
+    Ok(builder.build()?)
+}
+
+/// Fetch a file from the local Huggingface cache or download it into the cache - 
+/// interrupted downloads are resumed from the partial file in the cache and 
+/// transient failures are retried with exponential backoff by the API client
+fn huggingface_get(api: &Api, repo: hf_hub::Repo, file: &str, config: &DownloadConfig) -> Result<PathBuf, GptError> {
+    if let Some(path) = hf_hub::Cache::from_env().repo(repo.clone()).get(file) {
+        log::info!("Using cached file: {}", path.display());
+        return Ok(path)
+    }
+    let progress = DownloadProgress { bar: config.progress_bar() };
+    Ok(api.repo(repo).download_with_progress(file, progress)?)
+}
+
+/// Fetch a file at a revision of a Huggingface repository
+fn huggingface_fetch(endpoint: Option<&str>, repository: &str, revision: &str, file: &str, config: &DownloadConfig) -> Result<PathBuf, GptError> {
+    let api = huggingface_api(endpoint, config.retries)?;
+    let repo = hf_hub::Repo::with_revision(repository.to_string(), hf_hub::RepoType::Model, revision.to_string());
+    huggingface_get(&api, repo, file, config)
+}
+
+/// Download settings for remote model sources
+#[derive(Debug, Clone)]
+pub struct DownloadConfig {
+    /// Retries on transient failures
+    pub retries: usize,
+    /// Progress display shared between concurrent downloads
+    pub progress: Option<MultiProgress>
+}
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            progress: None
+        }
+    }
+}
+impl DownloadConfig {
+    fn progress_bar(&self) -> ProgressBar {
+        let bar = match &self.progress {
+            Some(multi) => multi.add(ProgressBar::new(0)),
+            None => ProgressBar::hidden()
+        };
+        bar.set_style(
+            ProgressStyle::with_template("{msg:<48} [{elapsed_precise}] [{wide_bar}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
+                .expect("Invalid progress template")
+                .progress_chars("=> ")
+        );
+        bar
+    }
+}
+
+struct DownloadProgress {
+    bar: ProgressBar
+}
+impl hf_hub::api::Progress for DownloadProgress {
+    fn init(&mut self, size: usize, filename: &str) {
+        // Called again on resume or retry with the full size followed by an update to the current position
+        self.bar.set_length(size as u64);
+        self.bar.set_position(0);
+        self.bar.set_message(filename.to_string());
+    }
+    fn update(&mut self, size: usize) {
+        self.bar.inc(size as u64);
+    }
+    fn finish(&mut self) {
+        self.bar.finish();
+    }
+}
+
+/// Resumable download over HTTP into a partial file next to the destination - returns 
+/// `false` if the file does not exist at the requested URL
+fn download_resumable(client: &reqwest::blocking::Client, url: &str, dest: &Path, config: &DownloadConfig) -> Result<bool, GptError> {
+
+    let part = PathBuf::from(format!("{}.part", dest.display()));
+    let bar = config.progress_bar();
+    bar.set_message(dest.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default());
+
+    let mut attempt = 0;
+    loop {
+        match download_range(client, url, &part, &bar) {
+            Ok(found) => {
+                if found {
+                    std::fs::rename(&part, dest)?;
+                    bar.finish();
+                } else {
+                    bar.finish_and_clear();
+                }
+                return Ok(found)
+            },
+            Err(err) => {
+                if attempt >= config.retries {
+                    bar.abandon();
+                    return Err(err)
+                }
+                let wait = std::time::Duration::from_millis(500 * 2u64.pow(attempt as u32)).min(std::time::Duration::from_secs(30));
+                log::warn!("Download failed ({err}) - resume in {:.1}s (retry {}/{})", wait.as_secs_f32(), attempt + 1, config.retries);
+                std::thread::sleep(wait);
+                attempt += 1;
+            }
+        }
+    }
+}
+
+fn download_range(client: &reqwest::blocking::Client, url: &str, part: &Path, bar: &ProgressBar) -> Result<bool, GptError> {
+
+    let start = if part.exists() { std::fs::metadata(part)?.len() } else { 0 };
+
+    let response = client.get(url)
+        .header(reqwest::header::RANGE, format!("bytes={start}-"))
+        .send()?;
+
+    let resumed = match response.status() {
+        reqwest::StatusCode::NOT_FOUND => return Ok(false),
+        reqwest::StatusCode::RANGE_NOT_SATISFIABLE if start > 0 => {
+            // Partial file is complete only if it has the size of the remote file
+            let size = client.head(url).send()?.error_for_status()?
+                .headers()
+                .get(reqwest::header::CONTENT_LENGTH)
+                .and_then(|length| length.to_str().ok())
+                .and_then(|length| length.parse::<u64>().ok());
+            if size == Some(start) {
+                return Ok(true)
+            }
+            log::warn!(
+                "Partial file size ({start} bytes) does not match the remote file size ({}) - restart download", 
+                size.map(|size| format!("{size} bytes")).unwrap_or("unknown".to_string())
+            );
+            std::fs::remove_file(part)?;
+            return download_range(client, url, part, bar)
+        },
+        reqwest::StatusCode::PARTIAL_CONTENT => true,
+        _ => false // servers without range support restart the download
+    };
+    let mut response = response.error_for_status()?;
+
+    let offset = if resumed { start } else { 0 };
+    bar.set_length(offset + response.content_length().unwrap_or(0));
+    bar.set_position(offset);
+
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(part)?;
+
+    let mut writer = bar.wrap_write(BufWriter::new(file));
+    response.copy_to(&mut writer)?;
+    writer.flush()?;
+
+    Ok(true)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ModelSourceType {
+    Huggingface,
+    Directory,
+    Archive,
+    Seaweedfs
+}
+
+/// Model sources from which model and tokenizer files can be fetched - mirrors 
+/// (local directories, archives and SeaweedFS) use the repository layout of 
+/// Huggingface (`{repository}/{file}`) or the flat layout of a model directory 
+/// produced by the download command (`{model_name}.gguf`) 
+#[derive(Debug, Clone)]
+pub enum ModelSource {
+    Huggingface(Option<String>),
+    Directory(PathBuf),
+    Archive(PathBuf),
+    SeaweedFs {
+        url: String,
+        path: String,
+        danger_invalid_certificate: bool
+    }
+}
+impl Default for ModelSource {
+    fn default() -> Self {
+        ModelSource::Huggingface(None)
+    }
+}
+impl ModelSource {
+    pub fn new(
+        source_type: ModelSourceType, 
+        endpoint: Option<String>, 
+        path: Option<PathBuf>, 
+        fs_url: &str, 
+        fs_port: &str, 
+        fs_path: &str, 
+        danger_invalid_certificate: bool
+    ) -> Result<Self, GptError> {
+        let source = match source_type {
+            ModelSourceType::Huggingface => ModelSource::Huggingface(endpoint),
+            ModelSourceType::Directory => ModelSource::Directory(path.ok_or(GptError::ModelSourcePathMissing)?),
+            ModelSourceType::Archive => ModelSource::Archive(path.ok_or(GptError::ModelSourcePathMissing)?),
+            ModelSourceType::Seaweedfs => ModelSource::SeaweedFs { 
+                url: format!("{}:{}", fs_url.trim_end_matches('/'), fs_port), 
+                path: fs_path.trim_matches('/').to_string(),
+                danger_invalid_certificate
+            }
+        };
+        Ok(source)
+    }
+    /// Origin recorded in the lock manifest
+    pub fn origin(&self) -> String {
+        match self {
+            ModelSource::Huggingface(endpoint) => endpoint.clone().unwrap_or(
+                std::env::var("HF_ENDPOINT").unwrap_or("https://huggingface.co".to_string())
+            ),
+            ModelSource::Directory(path) | ModelSource::Archive(path) => path.display().to_string(),
+            ModelSource::SeaweedFs { url, path, .. } => if path.is_empty() { url.clone() } else { format!("{url}/{path}") }
+        }
+    }
+    /// Fetch a repository file from the source into the destination path - returns 
+    /// the path of the fetched file in the Huggingface cache or the destination path
+    pub fn fetch(&self, repository: &str, revision: &str, file: &str, dest: &Path, config: &DownloadConfig) -> Result<PathBuf, GptError> {
+
+        let repository_file = Path::new(repository).join(file);
+        let flat_file = dest.file_name().map(PathBuf::from).unwrap_or_default();
+
+        match self {
+            ModelSource::Huggingface(endpoint) => {
+                let src = huggingface_fetch(endpoint.as_deref(), repository, revision, file, config)?;
+                std::fs::copy(&src, dest)?;
+                return Ok(src)
+            },
+            ModelSource::Directory(dir) => {
+                let src = [dir.join(&repository_file), dir.join(&flat_file)]
+                    .into_iter()
+                    .find(|path| path.exists())
+                    .ok_or(GptError::ModelSourceFileMissing(self.origin(), repository_file.display().to_string()))?;
+                
+                std::fs::copy(&src, dest)?;
+            },
+            ModelSource::Archive(archive) => {
+                let (reader, _format) = niffler::get_reader(
+                    Box::new(BufReader::new(File::open(archive)?))
+                )?;
+                let mut archive = tar::Archive::new(reader);
+                
+                let mut found = false;
+                for entry in archive.entries()? {
+                    let mut entry = entry?;
+                    let path = entry.path()?.to_path_buf();
+                    if path.ends_with(&repository_file) || path.file_name() == Some(flat_file.as_os_str()) {
+                        entry.unpack(dest)?;
+                        found = true;
+                        break;
+                    }
+                }
+                if !found {
+                    return Err(GptError::ModelSourceFileMissing(self.origin(), repository_file.display().to_string()))
+                }
+            },
+            ModelSource::SeaweedFs { danger_invalid_certificate, .. } => {
+                let client = reqwest::blocking::Client::builder()
+                    .danger_accept_invalid_certs(*danger_invalid_certificate)
+                    .timeout(None)
+                    .build()?;
+
+                let prefix = self.origin();
+
+                let mut found = false;
+                for candidate in [&repository_file, &flat_file] {
+                    let file_url = format!("{prefix}/{}", candidate.display());
+                    log::info!("Requesting file from SeaweedFS: {file_url}");
+                    if download_resumable(&client, &file_url, dest, config)? {
+                        found = true;
+                        break;
+                    }
+                }
+                if !found {
+                    return Err(GptError::ModelSourceFileMissing(self.origin(), repository_file.display().to_string()))
+                }
+            }
+        }
+        Ok(dest.to_path_buf())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelFileType {
+    Model,
+    Tokenizer
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelManifestEntry {
+    pub model: String,
+    pub file_type: ModelFileType,
+    pub file: String,
+    pub sha256: String,
+    pub size: u64,
+    pub repository: String,
+    pub revision: String,
+    pub commit: Option<String>,
+    pub source: String,
+    #[serde(default)]
+    pub origin: Option<String>,
+    pub date: String
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationStatus {
+    Verified,
+    Missing,
+    SizeMismatch,
+    ChecksumMismatch,
+    NotInManifest
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelVerification {
+    pub model: String,
+    pub file: String,
+    pub status: VerificationStatus,
+    pub expected: Option<String>,
+    pub observed: Option<String>
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelManifest {
+    pub files: Vec<ModelManifestEntry>
+}
+impl ModelManifest {
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(MODEL_MANIFEST)
+    }
+    pub fn from_json<P: AsRef<Path>>(path: P) -> Result<Self, GptError> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Err(GptError::ModelManifestMissing(path.display().to_string()))
+        }
+        let data = std::fs::read_to_string(path)?;
+        let manifest = serde_json::from_str::<ModelManifest>(&data)?;
+        Ok(manifest)
+    }
+    pub fn to_json(&self, path: &Path) -> Result<(), GptError> {
+        let writer = BufWriter::new(
+            File::create(path)?
+        );
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+    /// Read the manifest from the model directory or start a new one
+    pub fn from_dir_or_default(dir: &Path) -> Result<Self, GptError> {
+        let path = Self::path(dir);
+        if path.exists() {
+            Self::from_json(&path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+    /// Insert an entry, replacing any previous entry for the same file
+    pub fn insert(&mut self, entry: ModelManifestEntry) {
+        self.files.retain(|e| e.file != entry.file);
+        self.files.push(entry);
+    }
+    /// Re-hash the files in the model directory and compare them to the manifest -
+    /// if models are provided only their files are checked and models without
+    /// manifest entries are reported
+    pub fn verify(&self, dir: &Path, models: &[GeneratorModel]) -> Result<Vec<ModelVerification>, GptError> {
+
+        let names: Vec<&str> = models.iter().map(|m| m.model_name()).collect();
+
+        let mut report = Vec::new();
+        for entry in self.files.iter().filter(|e| names.is_empty() || names.contains(&e.model.as_str())) {
+            
+            let path = dir.join(&entry.file);
+
+            let (status, observed) = if !path.exists() {
+                (VerificationStatus::Missing, None)
+            } else {
+                log::info!("Computing checksum for file: {}", path.display());
+                let (sha256, size) = sha256_file(&path)?;
+                if size != entry.size {
+                    (VerificationStatus::SizeMismatch, Some(sha256))
+                } else if sha256 != entry.sha256 {
+                    (VerificationStatus::ChecksumMismatch, Some(sha256))
+                } else {
+                    (VerificationStatus::Verified, Some(sha256))
+                }
+            };
+
+            report.push(ModelVerification {
+                model: entry.model.clone(),
+                file: entry.file.clone(),
+                status,
+                expected: Some(entry.sha256.clone()),
+                observed
+            });
+        }
+
+        for model in models {
+            for file in [model.model_file(), model.tokenizer_file()] {
+                let file = file.display().to_string();
+                if !self.files.iter().any(|e| e.file == file) {
+                    report.push(ModelVerification {
+                        model: model.model_name().to_string(),
+                        file,
+                        status: VerificationStatus::NotInManifest,
+                        expected: None,
+                        observed: None
+                    });
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
 
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
@@ -26,9 +476,31 @@ impl ModelGroup {
             ModelGroup::DeepseekLlama => GeneratorModel::deepseek_llama(),
         }
     }
+    /// Selected models and models of the group without duplicates in order of selection
+    pub fn select(models: &[GeneratorModel], group: Option<ModelGroup>) -> Vec<GeneratorModel> {
+        let mut seen = HashSet::new();
+        models.iter()
+            .copied()
+            .chain(group.map(ModelGroup::to_models).unwrap_or_default())
+            .filter(|model| seen.insert(*model))
+            .collect()
+    }
 }
 
-#[derive(Clone, Debug, Copy, PartialEq, Eq, ValueEnum)]
+/// Presence and size of the model and tokenizer files in a model directory
+#[derive(Debug, Clone)]
+pub struct ModelFileStatus {
+    pub model: GeneratorModel,
+    pub model_size: Option<u64>,
+    pub tokenizer_size: Option<u64>
+}
+impl ModelFileStatus {
+    pub fn is_present(&self) -> bool {
+        self.model_size.is_some() && self.tokenizer_size.is_some()
+    }
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash, ValueEnum)]
 pub enum GeneratorModel {
 
     #[value(name = "gemma-3-27b-it-q8-0")]
@@ -102,40 +574,90 @@ impl GeneratorModel {
 
     /// Download and save the GGUF model file as `{model_name}.{ext}`
     pub fn save_model(&self, outdir: &Path) -> Result<PathBuf, GptError> {
-        let src = self.download_model()?;
-        std::fs::create_dir_all(outdir)?;
-        let dest = outdir.join(&self.model_file());
-        std::fs::copy(&src, &dest)?;
-        Ok(dest)
+        self.save_model_from(outdir, &ModelSource::default(), &DownloadConfig::default())
     }
     /// Download and save the tokenizer to `{model_name}.tokenizer.json`
     pub fn save_tokenizer(&self, outdir: &Path) -> Result<PathBuf, GptError> {
-        let src = self.download_tokenizer()?;
+        self.save_tokenizer_from(outdir, &ModelSource::default(), &DownloadConfig::default())
+    }
+    /// Fetch and save the GGUF model file from a model source
+    pub fn save_model_from(&self, outdir: &Path, source: &ModelSource, config: &DownloadConfig) -> Result<PathBuf, GptError> {
+        self.save_file(outdir, source, config, ModelFileType::Model)
+    }
+    /// Fetch and save the tokenizer from a model source
+    pub fn save_tokenizer_from(&self, outdir: &Path, source: &ModelSource, config: &DownloadConfig) -> Result<PathBuf, GptError> {
+        self.save_file(outdir, source, config, ModelFileType::Tokenizer)
+    }
+    fn save_file(&self, outdir: &Path, source: &ModelSource, config: &DownloadConfig, file_type: ModelFileType) -> Result<PathBuf, GptError> {
+
         std::fs::create_dir_all(outdir)?;
-        let dest = outdir.join(&self.tokenizer_file());
-        std::fs::copy(&src, &dest)?;
+
+        let (repository, revision, file, dest) = match file_type {
+            ModelFileType::Model => (self.model_repository(), self.model_revision(), self.model_config(), outdir.join(&self.model_file())),
+            ModelFileType::Tokenizer => (self.tokenizer_repository(), "main", "tokenizer.json", outdir.join(&self.tokenizer_file()))
+        };
+
+        let lock = fetch_lock(repository, file);
+        let _guard = lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        log::info!("Fetching {file} for {} from {}", self.model_name(), source.origin());
+        let src = source.fetch(repository, revision, file, &dest, config)?;
+
+        self.lock_file(outdir, &src, &dest, file_type, revision, source)?;
+
         Ok(dest)
     }
-    pub fn download_tokenizer(&self) -> Result<PathBuf, GptError> {
-        log::info!("Downloading tokenizer...");
-        let api = Api::new()?;
-        let repo = self.tokenizer_repository();
-        let api = api.model(repo.to_string());
-        let tokenizer_path = api.get("tokenizer.json")?;
-        Ok(tokenizer_path)
-    }
-    pub fn download_model(&self) -> Result<PathBuf, GptError> {
-        log::info!("Downloading model weights...");
-        let api = Api::new()?;
-        let repo = hf_hub::Repo::with_revision(
-            self.model_repository().to_string(),
-            hf_hub::RepoType::Model,
-            self.model_revision().to_string(),
-        );
-        let model_path = api.repo(repo).get(
-            self.model_config()
-        )?;
-        Ok(model_path)
+    /// Record checksum, size and source of a saved file in the lock manifest of the output directory - 
+    /// the revision is the revision the file was fetched from
+    fn lock_file(&self, outdir: &Path, src: &Path, dest: &Path, file_type: ModelFileType, revision: &str, model_source: &ModelSource) -> Result<(), GptError> {
+        
+        log::info!("Computing checksum for file: {}", dest.display());
+        let (sha256, size) = sha256_file(dest)?;
+
+        let (repository, source) = match file_type {
+            ModelFileType::Model => (self.model_repository(), self.model_config()),
+            ModelFileType::Tokenizer => (self.tokenizer_repository(), "tokenizer.json")
+        };
+
+        // Files in the Huggingface cache are stored in `snapshots/{commit}/{file}` 
+        // which allows us to record the resolved commit of the revision
+        let commit = match model_source {
+            ModelSource::Huggingface(_) => src.parent()
+                .filter(|snapshot| snapshot.parent().and_then(|p| p.file_name()).is_some_and(|p| p == "snapshots"))
+                .and_then(|snapshot| snapshot.file_name())
+                .map(|commit| commit.to_string_lossy().to_string()),
+            _ => None
+        };
+
+        let entry = ModelManifestEntry {
+            model: self.model_name().to_string(),
+            file_type,
+            file: dest.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default(),
+            sha256,
+            size,
+            repository: repository.to_string(),
+            revision: revision.to_string(),
+            commit,
+            source: source.to_string(),
+            origin: Some(model_source.origin()),
+            date: chrono::Utc::now().to_rfc3339()
+        };
+
+        let _lock = MANIFEST_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let mut manifest = ModelManifest::from_dir_or_default(outdir)?;
+        manifest.insert(entry);
+        manifest.to_json(&ModelManifest::path(outdir))?;
+
+        Ok(())
+    }
+    pub fn download_tokenizer(&self, endpoint: Option<&str>, config: &DownloadConfig) -> Result<PathBuf, GptError> {
+        log::info!("Downloading tokenizer for {}...", self.model_name());
+        huggingface_fetch(endpoint, self.tokenizer_repository(), "main", "tokenizer.json", config)
+    }
+    pub fn download_model(&self, endpoint: Option<&str>, config: &DownloadConfig) -> Result<PathBuf, GptError> {
+        log::info!("Downloading model weights for {}...", self.model_name());
+        huggingface_fetch(endpoint, self.model_repository(), self.model_revision(), self.model_config(), config)
     }
     pub fn tokenizer_repository(&self) -> &'static str {
         match self {
@@ -305,6 +827,15 @@ impl GeneratorModel {
         PathBuf::from(format!("{}.tokenizer.json", self.model_name()))
     }
 
+    pub fn file_status(&self, dir: &Path) -> ModelFileStatus {
+        let file_size = |file: PathBuf| std::fs::metadata(dir.join(file)).ok().map(|m| m.len());
+        ModelFileStatus {
+            model: *self,
+            model_size: file_size(self.model_file()),
+            tokenizer_size: file_size(self.tokenizer_file())
+        }
+    }
+
     pub fn get_eos_token(
         &self, 
         tos: &TokenOutputStream,
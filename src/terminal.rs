@@ -1,7 +1,11 @@
 use std::path::PathBuf;
 use clap::{Args, Parser, Subcommand};
 
-use crate::model::{GeneratorModel, ModelGroup};
+use crate::gpt::{TaskConfig, TreeConfig, TreeFormat};
+use crate::model::{GeneratorModel, ModelGroup, ModelSourceType};
+
+#[cfg(feature = "local")]
+use crate::gpt::{AgentPrimer, AssayContext, ClinicalField, TaxaColumn, TaxaFormat, TaxaSort};
 
 #[cfg(feature = "local")]
 use crate::text::TextGeneratorArgs;
@@ -92,6 +96,17 @@ pub struct App {
 pub enum Commands {
     /// Download local models and tokenizer configurations
     Download(DownloadArgs),
+    /// Verify downloaded model files against the lock manifest
+    Verify(VerifyArgs),
+    #[clap(subcommand)]
+    /// List and inspect models in a model directory
+    Models(ModelsCommands),
+    #[clap(subcommand)]
+    /// Export and work with diagnostic decision trees
+    Tree(TreeCommands),
+    #[cfg(feature = "local")]
+    /// Run the diagnostic agent on prefetched sample data with a local model
+    Diagnose(Box<DiagnoseArgs>),
     #[cfg(feature = "local")]
     /// Run local text generation on GPU
     Generate(TextGeneratorArgs),
@@ -108,7 +123,322 @@ pub struct DownloadArgs {
     pub group: Option<ModelGroup>,
     /// Output directory for downloads
     #[clap(long, short = 'o', default_value=".")]
-    pub outdir: PathBuf
+    pub outdir: PathBuf,
+    /// Model source to fetch files from
+    #[clap(long, short = 's', default_value="huggingface")]
+    pub source: ModelSourceType,
+    /// Huggingface-compatible mirror endpoint for Huggingface sources
+    #[clap(long, short = 'e', env = "HF_ENDPOINT")]
+    pub endpoint: Option<String>,
+    /// Model directory or archive (.tar, .tar.gz) for directory and archive sources
+    #[clap(long, short = 'l')]
+    pub path: Option<PathBuf>,
+    /// Model store path on SeaweedFS for SeaweedFS sources (uses --fs-url and --fs-port)
+    #[clap(long, default_value="/models")]
+    pub fs_path: String,
+    /// Number of models to download concurrently
+    #[clap(long, short = 'j', default_value="1")]
+    pub jobs: usize,
+    /// Retries with exponential backoff on transient download failures
+    #[clap(long, short = 'r', default_value="3")]
+    pub retries: usize,
+    /// Disable the download progress display
+    #[clap(long)]
+    pub no_progress: bool,
+}
+
+
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+    /// Models to verify (default: all files in the lock manifest)
+    #[clap(long, short = 'm', num_args(0..))]
+    pub models: Vec<GeneratorModel>,
+    /// Verify a predefined group of models (e.g. qwen, deepseek)
+    #[clap(long, short = 'g')]
+    pub group: Option<ModelGroup>,
+    /// Model directory containing the lock manifest
+    #[clap(long, short = 'd', default_value=".")]
+    pub dir: PathBuf,
+    /// Output verification report (.json)
+    #[clap(long, short = 'o')]
+    pub output: Option<PathBuf>,
+}
+
+
+#[derive(Debug, Subcommand)]
+pub enum ModelsCommands {
+    /// List present and missing models for each model group
+    List(ModelsListArgs),
+    #[cfg(feature = "local")]
+    /// Print the GGUF metadata of a model
+    Inspect(ModelsInspectArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ModelsListArgs {
+    /// List models of a predefined group (default: all groups)
+    #[clap(long, short = 'g')]
+    pub group: Option<ModelGroup>,
+    /// Model directory
+    #[clap(long, short = 'd', default_value=".")]
+    pub dir: PathBuf,
+    /// Only list models present in the model directory
+    #[clap(long, short = 'p')]
+    pub present: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ModelsInspectArgs {
+    /// Model to inspect
+    #[clap(long, short = 'm')]
+    pub model: GeneratorModel,
+    /// Model directory
+    #[clap(long, short = 'd', default_value=".")]
+    pub dir: PathBuf,
+    /// Print the quantization type of each tensor
+    #[clap(long, short = 't')]
+    pub tensors: bool,
+}
+
+
+#[derive(Debug, Subcommand)]
+pub enum TreeCommands {
+    /// Export a built-in decision tree to file (.json, .yaml)
+    Export(TreeExportArgs),
+    /// Validate the structure of a decision tree
+    Validate(TreeValidateArgs),
+    /// Draw the execution path of a sample on the decision tree (.dot, .mmd)
+    Overlay(TreeOverlayArgs),
+    /// Draw the consensus of the execution paths of a cohort on the decision tree (.svg)
+    Consensus(TreeConsensusArgs),
+    /// Compare two versions of a decision tree
+    Diff(TreeDiffArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct TreeExportArgs {
+    /// Decision tree file (.json, .yaml) - overrides the built-in decision tree
+    #[clap(long, short = 'f')]
+    pub tree_file: Option<PathBuf>,
+    /// Built-in decision tree to export
+    #[clap(long, short = 't', default_value="tiered")]
+    pub tree: TreeConfig,
+    /// Task configuration of the built-in decision tree
+    #[clap(long, short = 'c', default_value="default")]
+    pub task: TaskConfig,
+    /// Prompt library directory for the prompts of the built-in decision tree
+    #[clap(long)]
+    pub prompts: Option<PathBuf>,
+    /// Output decision tree file (.json, .yaml) or graph (.dot, .mmd)
+    #[clap(long, short = 'o')]
+    pub output: PathBuf,
+    /// Output format (default: from the output file extension)
+    #[clap(long, short = 'F')]
+    pub format: Option<TreeFormat>,
+}
+
+#[derive(Debug, Args)]
+pub struct TreeOverlayArgs {
+    /// Agent state of the sample (.json)
+    #[clap(long, short = 's')]
+    pub state: PathBuf,
+    /// Diagnostic result of the sample (.json) - final diagnosis including review outcomes
+    #[clap(long, short = 'r')]
+    pub result: Option<PathBuf>,
+    /// Decision tree file (.json, .yaml) - overrides the built-in decision tree
+    #[clap(long, short = 'f')]
+    pub tree_file: Option<PathBuf>,
+    /// Built-in decision tree
+    #[clap(long, short = 't', default_value="tiered")]
+    pub tree: TreeConfig,
+    /// Task configuration of the built-in decision tree
+    #[clap(long, short = 'c', default_value="default")]
+    pub task: TaskConfig,
+    /// Prompt library directory for the prompts of the built-in decision tree
+    #[clap(long)]
+    pub prompts: Option<PathBuf>,
+    /// Output graph (.dot, .mmd)
+    #[clap(long, short = 'o')]
+    pub output: PathBuf,
+    /// Output format (default: from the output file extension)
+    #[clap(long, short = 'F')]
+    pub format: Option<TreeFormat>,
+}
+
+#[derive(Debug, Args)]
+pub struct TreeConsensusArgs {
+    /// Directory of agent states (.json) of the cohort samples
+    #[clap(long, short = 's')]
+    pub states: PathBuf,
+    /// Decision tree file (.json, .yaml) - overrides the built-in decision tree
+    #[clap(long, short = 'f')]
+    pub tree_file: Option<PathBuf>,
+    /// Built-in decision tree
+    #[clap(long, short = 't', default_value="tiered")]
+    pub tree: TreeConfig,
+    /// Task configuration of the built-in decision tree
+    #[clap(long, short = 'c', default_value="default")]
+    pub task: TaskConfig,
+    /// Prompt library directory for the prompts of the built-in decision tree
+    #[clap(long)]
+    pub prompts: Option<PathBuf>,
+    /// Output consensus tree plot (.svg)
+    #[clap(long, short = 'o')]
+    pub output: PathBuf,
+    /// Output path frequencies by final diagnosis (.tsv)
+    #[clap(long, short = 'p')]
+    pub paths: Option<PathBuf>,
+    /// Plot width (pixels)
+    #[clap(long, default_value_t = 1200)]
+    pub width: u32,
+    /// Plot height (pixels)
+    #[clap(long, default_value_t = 800)]
+    pub height: u32,
+}
+
+#[derive(Debug, Args)]
+pub struct TreeDiffArgs {
+    /// Base decision tree file (.json, .yaml) - overrides the built-in base decision tree
+    #[clap(long, short = 'a')]
+    pub base_file: Option<PathBuf>,
+    /// Built-in base decision tree
+    #[clap(long, short = 't', default_value="tiered")]
+    pub tree: TreeConfig,
+    /// Task configuration of the built-in base decision tree
+    #[clap(long, short = 'c', default_value="default")]
+    pub task: TaskConfig,
+    /// Prompt library directory for the prompts of the built-in base decision tree and built-in includes
+    #[clap(long)]
+    pub prompts: Option<PathBuf>,
+    /// Compared decision tree file (.json, .yaml)
+    #[clap(long, short = 'b')]
+    pub tree_file: PathBuf,
+    /// Output changes (.json)
+    #[clap(long, short = 'o')]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct TreeValidateArgs {
+    /// Decision tree file (.json, .yaml) - overrides the built-in decision tree
+    #[clap(long, short = 'f')]
+    pub tree_file: Option<PathBuf>,
+    /// Built-in decision tree
+    #[clap(long, short = 't', default_value="tiered")]
+    pub tree: TreeConfig,
+    /// Task configuration of the built-in decision tree
+    #[clap(long, short = 'c', default_value="default")]
+    pub task: TaskConfig,
+    /// Prompt library directory for the prompts of the built-in decision tree
+    #[clap(long)]
+    pub prompts: Option<PathBuf>,
+    /// Output validation report (.json)
+    #[clap(long, short = 'o')]
+    pub output: Option<PathBuf>,
+}
+
+#[cfg(feature = "local")]
+#[derive(Debug, Args)]
+pub struct DiagnoseArgs {
+    /// Prefetched sample data (.json)
+    #[clap(long, short = 'p')]
+    pub prefetch: PathBuf,
+    /// Decision tree file (.json, .yaml) - overrides the built-in decision tree
+    #[clap(long, short = 'f')]
+    pub tree_file: Option<PathBuf>,
+    /// Built-in decision tree
+    #[clap(long, short = 't', default_value="tiered")]
+    pub tree: TreeConfig,
+    /// Task configuration of the built-in decision tree
+    #[clap(long, short = 'c', default_value="default")]
+    pub task: TaskConfig,
+    /// Prompt library directory for the primer, assay context and built-in decision tree prompts
+    #[clap(long)]
+    pub prompts: Option<PathBuf>,
+    /// Few-shot example directory for nodes that reference examples
+    #[clap(long)]
+    pub examples: Option<PathBuf>,
+    /// Host copy-number evidence for aneuploidy nodes (.json)
+    #[clap(long)]
+    pub aneuploidy: Option<PathBuf>,
+    /// Post-filter configuration applied to the prefetched taxa (.json)
+    #[clap(long)]
+    pub post_filter: Option<PathBuf>,
+    /// Sample type of the sample type catalogue for the prompts (e.g. csf, bal, stool or none)
+    #[clap(long, short = 's')]
+    pub sample_context: Option<String>,
+    /// Sample type catalogue extending the built-in sample types (.yaml, .json)
+    #[clap(long)]
+    pub sample_catalogue: Option<PathBuf>,
+    /// Clinical notes for the prompts
+    #[clap(long, conflicts_with_all = ["clinical", "sample_sheet"])]
+    pub clinical_notes: Option<String>,
+    /// Clinical record for the prompts (.json)
+    #[clap(long, conflicts_with = "sample_sheet")]
+    pub clinical: Option<PathBuf>,
+    /// Sample sheet with a sample column and a clinical record (JSON) or clinical notes column (.tsv)
+    #[clap(long)]
+    pub sample_sheet: Option<PathBuf>,
+    /// Clinical column of the sample sheet
+    #[clap(long, default_value = "clinical")]
+    pub clinical_column: String,
+    /// Clinical record fields removed from the prompts for ablation experiments - requires a structured clinical record
+    #[clap(long, num_args(0..))]
+    pub ablate: Vec<ClinicalField>,
+    /// Format of the candidate taxa for nodes without a renderer
+    #[clap(long, default_value = "prose")]
+    pub taxa_format: TaxaFormat,
+    /// Columns of the tabular taxa formats
+    #[clap(long, num_args(0..), default_values = ["name", "domain", "rpm", "contigs", "bases"])]
+    pub taxa_columns: Vec<TaxaColumn>,
+    /// Order of the candidate taxa
+    #[clap(long, default_value = "input")]
+    pub taxa_sort: TaxaSort,
+    /// Laboratory contaminant registry annotating matching taxa (.yaml, .json)
+    #[clap(long)]
+    pub contaminants: Option<PathBuf>,
+    /// Assay identifier of the contaminant registry entries - defaults to the assay context
+    #[clap(long)]
+    pub contaminant_assay: Option<String>,
+    /// Assay context for the prompts
+    #[clap(long, short = 'a')]
+    pub assay_context: Option<AssayContext>,
+    /// Agent primer for the prompts
+    #[clap(long)]
+    pub agent_primer: Option<AgentPrimer>,
+    /// Disable thinking mode for models that support it
+    #[clap(long)]
+    pub disable_thinking: bool,
+    /// Text generation model
+    #[clap(long, short = 'm', default_value = "qwen3-32b-q8-0")]
+    pub model: GeneratorModel,
+    /// Model file and download directory
+    #[clap(long, short = 'd', default_value=".")]
+    pub model_dir: PathBuf,
+    /// The length of the sample to generate (in tokens)
+    #[clap(long, short = 'n', default_value_t = 8192)]
+    pub sample_len: usize,
+    /// The temperature used to generate samples
+    #[clap(long, default_value_t = 0.6)]
+    pub temperature: f64,
+    /// Only sample among the top K samples
+    #[clap(long)]
+    pub top_k: Option<usize>,
+    /// Nucleus sampling probability cutoff
+    #[clap(long)]
+    pub top_p: Option<f64>,
+    /// Minimum probability threshold for sampling
+    #[clap(long)]
+    pub min_p: Option<f64>,
+    /// GPU device index to run on
+    #[clap(long, short = 'g', default_value_t = 0)]
+    pub gpu: usize,
+    /// Output diagnostic result (.json)
+    #[clap(long, short = 'o')]
+    pub output: PathBuf,
+    /// Output agent state (.json)
+    #[clap(long)]
+    pub state: Option<PathBuf>,
 }
 
 
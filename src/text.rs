@@ -1,4 +1,7 @@
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::path::{Path, PathBuf};
 use clap::{Args, ValueEnum};
 use std::io::Write;
 
@@ -16,9 +19,13 @@ use candle_transformers::models::quantized_gemma3 as gemma3;
 use candle_transformers::utils::apply_repeat_penalty;
 use candle_transformers::generation::{LogitsProcessor, Sampling};
 
+use async_openai::Client;
+use async_openai::types::{ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequestArgs};
+
 use crate::model::GeneratorModel;
+use crate::gpt::{GptModel, NodeGeneration, NodeModel};
 use crate::error::GptError;
-use crate::utils::TokenOutputStream;
+use crate::utils::{format_size, TokenOutputStream};
 
 // 'min_p' filter implementation for LogitsProcessor
 // that mirrors implementation in llama.cpp:
@@ -226,22 +233,32 @@ impl TextGenerator {
         prompt: &str,
         disable_thinking: bool
     ) -> Result<(String, String), GptError> {
+        let config = self.config.clone();
+        self.run_with(prompt, disable_thinking, &config)
+    }
+    /// Run with the sampling parameters of another configuration for the loaded model
+    pub fn run_with(
+        &mut self,
+        prompt: &str,
+        disable_thinking: bool,
+        config: &GeneratorConfig
+    ) -> Result<(String, String), GptError> {
 
         let mut tos = TokenOutputStream::new(
             self.tokenizer.clone()
         );
 
-        let prompt = if self.config.raw_prompt {
+        let prompt = if config.raw_prompt {
             prompt.to_string()
         } else {
-            self.config.model.format_prompt(prompt, disable_thinking)
+            config.model.format_prompt(prompt, disable_thinking)
         };
 
-        if self.config.log_info {
+        if config.log_info {
             log::info!("Prompt is: \n\n{prompt}\n\n");
         }
 
-        let min_p_filter: Option<Box<dyn LogitsFilter>> = self.config.min_p.map(|min_p| {
+        let min_p_filter: Option<Box<dyn LogitsFilter>> = config.min_p.map(|min_p| {
             Box::new(MinPFilter { min_p: min_p as f32 }) as Box<dyn LogitsFilter>
         });
 
@@ -250,9 +267,9 @@ impl TextGenerator {
             .encode(prompt, true)?;
 
         let mut tokens = tokens.get_ids().to_vec();
-        let to_sample = self.config.sample_len.saturating_sub(1);
+        let to_sample = config.sample_len.saturating_sub(1);
 
-        match self.config.model {
+        match config.model {
             GeneratorModel::DeepseekR1Llama8bQ4KM => {
                 tokens = if tokens.len() + to_sample > llama::MAX_SEQ_LEN - 10 {
                     let to_remove = tokens.len() + to_sample + 10 - llama::MAX_SEQ_LEN;
@@ -293,15 +310,15 @@ impl TextGenerator {
         }
 
         let mut logits_processor = TextGenerator::build_logits_processor(
-            self.config.temperature, 
-            self.config.seed, 
-            self.config.top_k, 
-            self.config.top_p
+            config.temperature, 
+            config.seed, 
+            config.top_k, 
+            config.top_p
         );
 
         log::info!("Build model architecture with weights.");
         
-        let eos_token = self.config.model.get_eos_token(&tos)?;
+        let eos_token = config.model.get_eos_token(&tos)?;
 
         log::info!("Start generative processing and sampling tokens.");
         let (thoughts, answer) = TextGenerator::generate(
@@ -310,12 +327,12 @@ impl TextGenerator {
             &tokens,
             &mut tos,
             &mut logits_processor,
-            self.config.sample_len,
-            self.config.repeat_penalty,
-            self.config.repeat_last_n,
+            config.sample_len,
+            config.repeat_penalty,
+            config.repeat_last_n,
             eos_token,
-            self.config.split_prompt,
-            self.config.log_info,
+            config.split_prompt,
+            config.log_info,
             min_p_filter.as_deref(),        
         )?;
 
@@ -512,16 +529,12 @@ impl TextGenerator {
     }
     pub fn gguf_info(gguf: &gguf_file::Content, start: &std::time::Instant) {
 
-        let mut total_size_in_bytes = 0;
-        for (_, tensor) in gguf.tensor_infos.iter() {
-            let elem_count = tensor.shape.elem_count();
-            total_size_in_bytes +=
-                elem_count * tensor.ggml_dtype.type_size() / tensor.ggml_dtype.block_size();
-        }
+        let summary = GgufSummary::from_content(gguf);
+
         log::info!(
             "loaded {:?} tensors ({}) in {:.2}s",
-            gguf.tensor_infos.len(),
-            &format_size(total_size_in_bytes),
+            summary.tensors,
+            &format_size(summary.total_size),
             start.elapsed().as_secs_f32(),
         );
     }
@@ -546,6 +559,178 @@ impl TextGenerator {
     }
 }
 
+/// Local text generators of the decision tree nodes loaded on first use, 
+/// and remote models of nodes with a remote model override
+pub struct ModelPool {
+    /// Default model and sampling parameters of nodes without overrides
+    pub config: GeneratorConfig,
+    generators: HashMap<GeneratorModel, TextGenerator>
+}
+impl ModelPool {
+    pub fn new(config: GeneratorConfig) -> Self {
+        Self { config, generators: HashMap::new() }
+    }
+    pub fn loaded(&self) -> Vec<GeneratorModel> {
+        self.generators.keys().copied().collect()
+    }
+    /// Run the prompt with the model and sampling overrides of a node
+    pub fn run(
+        &mut self, 
+        prompt: &str, 
+        generation: Option<&NodeGeneration>, 
+        disable_thinking: bool
+    ) -> Result<(String, String), GptError> {
+
+        let generation = generation.cloned().unwrap_or_default();
+        let disable_thinking = generation.thinking.map(|thinking| !thinking).unwrap_or(disable_thinking);
+
+        match generation.model.unwrap_or(NodeModel::Local(self.config.model)) {
+            NodeModel::Local(model) => {
+                let config = GeneratorConfig {
+                    model,
+                    ..self.config.clone()
+                };
+                let generator = match self.generators.entry(model) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        log::info!("Loading node model: {}", NodeModel::Local(model));
+                        entry.insert(TextGenerator::new(config.clone())?)
+                    }
+                };
+                let sampling = GeneratorConfig {
+                    temperature: generation.temperature.unwrap_or(config.temperature),
+                    sample_len: generation.sample_len.unwrap_or(config.sample_len),
+                    ..config
+                };
+                generator.run_with(prompt, disable_thinking, &sampling)
+            },
+            NodeModel::Remote(model) => self.run_remote(model, prompt, &generation)
+        }
+    }
+    /// Remote models use the provider defaults unless the node sets a temperature
+    fn run_remote(&self, model: GptModel, prompt: &str, generation: &NodeGeneration) -> Result<(String, String), GptError> {
+        
+        if !model.is_openai() {
+            return Err(GptError::RemoteModelNotSupported(String::from(&model)))
+        }
+
+        let mut request = CreateChatCompletionRequestArgs::default();
+        request
+            .model(String::from(&model))
+            .max_completion_tokens(generation.sample_len.unwrap_or(self.config.sample_len) as u32)
+            .messages(vec![
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(prompt)
+                    .build()?
+                    .into()
+            ]);
+        if let Some(temperature) = generation.temperature {
+            request.temperature(temperature as f32);
+        }
+        let request = request.build()?;
+
+        let client = Client::new();
+        let response = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => tokio::task::block_in_place(|| handle.block_on(client.chat().create(request)))?,
+            Err(_) => tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?
+                .block_on(client.chat().create(request))?
+        };
+
+        let answer = response.choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .unwrap_or_default();
+
+        Ok((String::new(), answer))
+    }
+}
+
+
+/// Summary of the metadata and tensors of a GGUF model file
+#[derive(Debug, Clone)]
+pub struct GgufSummary {
+    pub architecture: Option<String>,
+    pub name: Option<String>,
+    pub context_length: Option<u64>,
+    pub tensors: usize,
+    pub parameters: usize,
+    pub total_size: usize,
+    pub quantization: BTreeMap<String, usize>,
+    pub tensor_types: BTreeMap<String, String>,
+}
+impl GgufSummary {
+    pub fn from_file(path: &Path) -> Result<Self, GptError> {
+        let mut file = std::fs::File::open(path)?;
+        let gguf = gguf_file::Content::read(&mut file)
+            .map_err(|e| e.with_path(path))?;
+        Ok(Self::from_content(&gguf))
+    }
+    pub fn from_content(gguf: &gguf_file::Content) -> Self {
+
+        let metadata_string = |key: &str| match gguf.metadata.get(key) {
+            Some(gguf_file::Value::String(value)) => Some(value.clone()),
+            _ => None
+        };
+
+        let architecture = metadata_string("general.architecture");
+        let name = metadata_string("general.name");
+
+        let context_length = architecture.as_ref().and_then(|arch| {
+            match gguf.metadata.get(&format!("{arch}.context_length")) {
+                Some(gguf_file::Value::U32(value)) => Some(*value as u64),
+                Some(gguf_file::Value::U64(value)) => Some(*value),
+                _ => None
+            }
+        });
+
+        let mut parameters = 0;
+        let mut total_size = 0;
+        let mut quantization = BTreeMap::new();
+        let mut tensor_types = BTreeMap::new();
+
+        for (tensor_name, tensor) in gguf.tensor_infos.iter() {
+            let elem_count = tensor.shape.elem_count();
+            let dtype = format!("{:?}", tensor.ggml_dtype);
+
+            parameters += elem_count;
+            total_size += elem_count * tensor.ggml_dtype.type_size() / tensor.ggml_dtype.block_size();
+
+            *quantization.entry(dtype.clone()).or_insert(0) += 1;
+            tensor_types.insert(tensor_name.clone(), dtype);
+        }
+
+        Self {
+            architecture,
+            name,
+            context_length,
+            tensors: gguf.tensor_infos.len(),
+            parameters,
+            total_size,
+            quantization,
+            tensor_types
+        }
+    }
+    pub fn print(&self, tensors: bool) {
+        println!("{:<20} {}", "Name", self.name.as_deref().unwrap_or("-"));
+        println!("{:<20} {}", "Architecture", self.architecture.as_deref().unwrap_or("-"));
+        println!("{:<20} {}", "Context length", self.context_length.map_or("-".to_string(), |c| c.to_string()));
+        println!("{:<20} {:.2}B", "Parameters", self.parameters as f64 / 1e9);
+        println!("{:<20} {}", "Tensors", self.tensors);
+        println!("{:<20} {}", "Total size", format_size(self.total_size));
+        println!("{:<20} {}", "Quantization", self.quantization.iter().map(|(dtype, n)| format!("{dtype} ({n})")).collect::<Vec<_>>().join(", "));
+
+        if tensors {
+            println!();
+            for (tensor_name, dtype) in &self.tensor_types {
+                println!("{:<60} {}", tensor_name, dtype);
+            }
+        }
+    }
+}
+
 fn split_think(text: &str) -> (String, String) {
     // Split at most once on the delimiter
     let mut parts = text.splitn(2, "</think>");
@@ -587,18 +772,6 @@ impl ModelGroup {
     }
 }
 
-fn format_size(size_in_bytes: usize) -> String {
-    if size_in_bytes < 1_000 {
-        format!("{}B", size_in_bytes)
-    } else if size_in_bytes < 1_000_000 {
-        format!("{:.2}KB", size_in_bytes as f64 / 1e3)
-    } else if size_in_bytes < 1_000_000_000 {
-        format!("{:.2}MB", size_in_bytes as f64 / 1e6)
-    } else {
-        format!("{:.2}GB", size_in_bytes as f64 / 1e9)
-    }
-}
-
 
 #[derive(Args, Debug, Clone)]
 pub struct TextGeneratorArgs {
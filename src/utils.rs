@@ -8,6 +8,7 @@ use env_logger::fmt::Color;
 use log::{LevelFilter, Level};
 use niffler::{get_reader, get_writer};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 use crate::error::GptError;
 
@@ -50,6 +51,18 @@ impl UuidUtils for uuid::Uuid {
     }
 }
 
+pub fn format_size(size_in_bytes: usize) -> String {
+    if size_in_bytes < 1_000 {
+        format!("{}B", size_in_bytes)
+    } else if size_in_bytes < 1_000_000 {
+        format!("{:.2}KB", size_in_bytes as f64 / 1e3)
+    } else if size_in_bytes < 1_000_000_000 {
+        format!("{:.2}MB", size_in_bytes as f64 / 1e6)
+    } else {
+        format!("{:.2}GB", size_in_bytes as f64 / 1e9)
+    }
+}
+
 pub fn init_logger() {
 
     Builder::new()
@@ -141,6 +154,22 @@ pub fn read_tsv<T: for<'de>Deserialize<'de>>(file: &Path, flexible: bool, header
     Ok(records)
 }
 
+/// Hexadecimal SHA-256 digest of a text
+pub fn sha256_str(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+/// Computes the SHA-256 checksum of a file as lowercase hex string
+/// and returns it together with the file size in bytes.
+pub fn sha256_file(file: &Path) -> Result<(String, u64), GptError> {
+
+    let mut reader = BufReader::new(File::open(file)?);
+    let mut hasher = Sha256::new();
+    let size = std::io::copy(&mut reader, &mut hasher)?;
+
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
+
 
 /// This is a wrapper around a tokenizer to ensure that tokens can be returned to the user in a
 /// streaming way rather than having to wait for the full decoding.